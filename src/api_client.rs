@@ -1,29 +1,140 @@
-use std::fmt::Display;
+use std::fmt::{self, Display};
+use std::time::Duration;
 
 use crate::prelude::*;
+use crate::request_client::client_builder;
 use crate::{app::Cli, config::CodSpeedConfig};
 use console::style;
-use gql_client::{Client as GQLClient, ClientConfig};
 use nestify::nest;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
+const GQL_TIMEOUT: Duration = Duration::from_secs(10);
+
 pub struct CodSpeedAPIClient {
     gql_client: GQLClient,
     unauthenticated_gql_client: GQLClient,
 }
 
+/// A minimal GraphQL client, built on the reqwest client of the runner so that it trusts the
+/// certificate authorities of `--ca-bundle`
+struct GQLClient {
+    endpoint: String,
+    authorization: Option<String>,
+    client: reqwest::Client,
+}
+
+#[derive(Deserialize, Debug)]
+struct GraphQLErrorExtensions {
+    code: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GraphQLErrorMessage {
+    message: String,
+    extensions: Option<GraphQLErrorExtensions>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GraphQLResponse<K> {
+    data: Option<K>,
+    errors: Option<Vec<GraphQLErrorMessage>>,
+}
+
+#[derive(Debug)]
+enum GraphQLError {
+    Request(reqwest::Error),
+    /// A response that is not a GraphQL response, with its status
+    InvalidResponse(reqwest::StatusCode),
+    Response(Vec<GraphQLErrorMessage>),
+}
+
+impl GraphQLError {
+    fn contains_error_code(&self, code: &str) -> bool {
+        match self {
+            GraphQLError::Request(_) | GraphQLError::InvalidResponse(_) => false,
+            GraphQLError::Response(errors) => errors.iter().any(|error| {
+                error
+                    .extensions
+                    .as_ref()
+                    .and_then(|extensions| extensions.code.as_deref())
+                    == Some(code)
+            }),
+        }
+    }
+}
+
+impl Display for GraphQLError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphQLError::Request(error) => write!(f, "{}", error),
+            GraphQLError::InvalidResponse(status) => write!(f, "Invalid response: {}", status),
+            GraphQLError::Response(errors) => write!(
+                f,
+                "{}",
+                errors.iter().map(|error| error.message.as_str()).join(", ")
+            ),
+        }
+    }
+}
+
+impl GQLClient {
+    async fn query_with_optional_vars<K: DeserializeOwned, T: Serialize>(
+        &self,
+        query: &str,
+        variables: Option<T>,
+    ) -> std::result::Result<K, GraphQLError> {
+        let mut request = self
+            .client
+            .post(&self.endpoint)
+            .json(&serde_json::json!({ "query": query, "variables": variables }));
+        if let Some(authorization) = &self.authorization {
+            request = request.header("Authorization", authorization);
+        }
+        let response = request.send().await.map_err(GraphQLError::Request)?;
+        let status = response.status();
+        let body = response.bytes().await.map_err(GraphQLError::Request)?;
+        // The errors of a GraphQL response can come with an error status
+        let response = serde_json::from_slice::<GraphQLResponse<K>>(&body)
+            .map_err(|_| GraphQLError::InvalidResponse(status))?;
+        match (response.data, response.errors) {
+            (_, Some(errors)) if !errors.is_empty() => Err(GraphQLError::Response(errors)),
+            (Some(data), _) => Ok(data),
+            (None, _) => Err(GraphQLError::Response(vec![GraphQLErrorMessage {
+                message: "The response has no data".into(),
+                extensions: None,
+            }])),
+        }
+    }
+
+    async fn query_unwrap<K: DeserializeOwned>(
+        &self,
+        query: &str,
+    ) -> std::result::Result<K, GraphQLError> {
+        self.query_with_optional_vars::<K, ()>(query, None).await
+    }
+
+    async fn query_with_vars_unwrap<K: DeserializeOwned, T: Serialize>(
+        &self,
+        query: &str,
+        variables: T,
+    ) -> std::result::Result<K, GraphQLError> {
+        self.query_with_optional_vars(query, Some(variables)).await
+    }
+}
+
 impl TryFrom<&Cli> for CodSpeedAPIClient {
     type Error = Error;
     fn try_from(args: &Cli) -> Result<Self> {
         let codspeed_config = CodSpeedConfig::load()?;
 
         Ok(Self {
-            gql_client: build_gql_api_client(&codspeed_config, args.api_url.clone(), true),
+            gql_client: build_gql_api_client(&codspeed_config, args.api_url.clone(), true)?,
             unauthenticated_gql_client: build_gql_api_client(
                 &codspeed_config,
                 args.api_url.clone(),
                 false,
-            ),
+            )?,
         })
     }
 }
@@ -32,23 +143,14 @@ fn build_gql_api_client(
     codspeed_config: &CodSpeedConfig,
     api_url: String,
     with_auth: bool,
-) -> GQLClient {
-    let headers = if with_auth && codspeed_config.auth.token.is_some() {
-        let mut headers = std::collections::HashMap::new();
-        headers.insert(
-            "Authorization".to_string(),
-            codspeed_config.auth.token.clone().unwrap(),
-        );
-        headers
-    } else {
-        Default::default()
-    };
-
-    GQLClient::new_with_config(ClientConfig {
+) -> Result<GQLClient> {
+    Ok(GQLClient {
         endpoint: api_url,
-        timeout: Some(10),
-        headers: Some(headers),
-        proxy: None,
+        authorization: codspeed_config.auth.token.clone().filter(|_| with_auth),
+        client: client_builder()
+            .timeout(GQL_TIMEOUT)
+            .build()
+            .context("Failed to build the API client")?,
     })
 }
 
@@ -214,3 +316,57 @@ impl CodSpeedAPIClient {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::mock_api::MockApiServer;
+
+    use super::*;
+
+    fn mock_gql_client(mock_api_server: &MockApiServer) -> GQLClient {
+        GQLClient {
+            endpoint: mock_api_server.graphql_url(),
+            authorization: Some("token".into()),
+            client: client_builder().build().unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_query_with_vars() {
+        let mock_api_server = MockApiServer::start().unwrap();
+        let response = mock_gql_client(&mock_api_server)
+            .query_with_vars_unwrap::<FetchLocalRunReportData, _>(
+                include_str!("queries/FetchLocalRunReport.gql"),
+                FetchLocalRunReportVars {
+                    owner: "owner".into(),
+                    name: "name".into(),
+                    run_id: "run-id".into(),
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.repository.runs[0].status, RunStatus::Completed);
+    }
+
+    #[tokio::test]
+    async fn test_query_errors() {
+        let mock_api_server = MockApiServer::start().unwrap();
+        let error = mock_gql_client(&mock_api_server)
+            .query_unwrap::<CreateLoginSessionData>(include_str!("queries/CreateLoginSession.gql"))
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Operation not supported by the mock API");
+        assert!(!error.contains_error_code("UNAUTHENTICATED"));
+    }
+
+    #[test]
+    fn test_contains_error_code() {
+        let response: GraphQLResponse<()> = serde_json::from_str(
+            r#"{"errors": [{"message": "Unauthenticated", "extensions": {"code": "UNAUTHENTICATED"}}]}"#,
+        )
+        .unwrap();
+        let error = GraphQLError::Response(response.errors.unwrap());
+        assert!(error.contains_error_code("UNAUTHENTICATED"));
+        assert!(!error.contains_error_code("FORBIDDEN"));
+    }
+}
@@ -1,10 +1,16 @@
 use crate::{
-    api_client::CodSpeedAPIClient, auth, local_logger::CODSPEED_U8_COLOR_CODE, prelude::*, run,
+    api_client::CodSpeedAPIClient,
+    auth,
+    local_logger::CODSPEED_U8_COLOR_CODE,
+    mock_api::{MockApiServer, MOCK_API_TOKEN},
+    prelude::*,
+    request_client, run,
 };
 use clap::{
     builder::{styling, Styles},
-    Parser, Subcommand,
+    Parser, Subcommand, ValueEnum,
 };
+use std::path::PathBuf;
 
 fn create_styles() -> Styles {
     styling::Styles::styled()
@@ -29,25 +35,80 @@ pub struct Cli {
     )]
     pub api_url: String,
 
+    /// The API to send requests to. `mock` starts a local stub of the CodSpeed API that logs
+    /// every received request, useful to validate a CI configuration without uploading results
+    #[arg(
+        long,
+        env = "CODSPEED_API_MODE",
+        global = true,
+        value_enum,
+        default_value_t = ApiMode::Production
+    )]
+    pub api_mode: ApiMode,
+
+    /// Path to a PEM bundle of the certificate authorities to trust in addition to the system
+    /// ones, e.g. to go through a TLS-intercepting proxy. HTTP proxies are configured with the
+    /// standard `HTTPS_PROXY` and `NO_PROXY` environment variables.
+    #[arg(long, env = "CODSPEED_CA_BUNDLE", global = true)]
+    pub ca_bundle: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Commands,
 }
 
+#[derive(ValueEnum, Clone, Debug, PartialEq)]
+pub enum ApiMode {
+    Production,
+    Mock,
+}
+
 #[derive(Subcommand, Debug)]
+#[allow(clippy::large_enum_variant)]
 enum Commands {
     /// Run the bench command and upload the results to CodSpeed
     Run(run::RunArgs),
     /// Commands related to authentication with CodSpeed
     Auth(auth::AuthArgs),
+    /// Upload a bundle written by `codspeed run --output`, or the profile folder of a run
+    Upload(run::UploadArgs),
+    /// Replay the upload of a session recorded with `codspeed run --record-session`
+    Replay(run::ReplayArgs),
 }
 
 pub async fn run() -> Result<()> {
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
+    let mock_api_server = match cli.api_mode {
+        ApiMode::Production => None,
+        ApiMode::Mock => {
+            let mock_api_server = MockApiServer::start()?;
+            cli.api_url = mock_api_server.graphql_url();
+            Some(mock_api_server)
+        }
+    };
+    // The CA bundle must be set before any client is built
+    if let Some(ca_bundle) = &cli.ca_bundle {
+        request_client::set_ca_bundle(ca_bundle)?;
+    }
     let api_client = CodSpeedAPIClient::try_from(&cli)?;
 
     match cli.command {
-        Commands::Run(args) => run::run(args, &api_client).await?,
+        Commands::Run(mut args) => {
+            if let Some(mock_api_server) = &mock_api_server {
+                args.upload_url = Some(mock_api_server.upload_url());
+                args.token.get_or_insert_with(|| MOCK_API_TOKEN.into());
+                args.mock_api = true;
+            }
+            run::run(args, &api_client).await?
+        }
         Commands::Auth(args) => auth::run(args, &api_client).await?,
+        Commands::Upload(mut args) => {
+            if let Some(mock_api_server) = &mock_api_server {
+                args.upload_url = Some(mock_api_server.upload_url());
+                args.token.get_or_insert_with(|| MOCK_API_TOKEN.into());
+            }
+            run::upload(args, &api_client).await?
+        }
+        Commands::Replay(args) => run::replay(args, &api_client).await?,
     }
     Ok(())
 }
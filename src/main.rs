@@ -4,6 +4,7 @@ mod auth;
 mod config;
 mod local_logger;
 mod logger;
+mod mock_api;
 mod prelude;
 mod request_client;
 mod run;
@@ -36,6 +37,9 @@ async fn main() {
         }
         clean_logger();
 
+        if run::is_interrupted_error(&err) {
+            std::process::exit(run::INTERRUPTED_EXIT_CODE);
+        }
         std::process::exit(1);
     }
 }
@@ -0,0 +1,272 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+use serde_json::{json, Value};
+
+use crate::prelude::*;
+
+/// Token sent to the mock API, which does not check authentication
+pub const MOCK_API_TOKEN: &str = "mock-token";
+const MOCK_RUN_ID: &str = "mock-run-id";
+
+/// A local stub of the CodSpeed API, logging every request it receives.
+///
+/// It answers the upload flow and the run report query with successful responses, so that a
+/// whole CI configuration can be validated without contacting the production API.
+pub struct MockApiServer {
+    base_url: String,
+}
+
+struct MockRequest {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+/// Read a body sent with `Transfer-Encoding: chunked`, e.g. the throttled uploads
+fn read_chunked_body(reader: &mut impl BufRead) -> Result<Vec<u8>> {
+    let mut body = vec![];
+    loop {
+        let mut chunk_size = String::new();
+        reader.read_line(&mut chunk_size)?;
+        let chunk_size = chunk_size.split(';').next().unwrap_or_default().trim();
+        let chunk_size = usize::from_str_radix(chunk_size, 16)
+            .context(format!("Invalid chunk size: {}", chunk_size))?;
+        if chunk_size == 0 {
+            break;
+        }
+        let start = body.len();
+        body.resize(start + chunk_size, 0);
+        reader.read_exact(&mut body[start..])?;
+        // Skip the CRLF ending the chunk
+        reader.read_line(&mut String::new())?;
+    }
+    // Skip the trailers up to the empty line ending the request
+    loop {
+        let mut trailer = String::new();
+        if reader.read_line(&mut trailer)? == 0 || trailer.trim_end().is_empty() {
+            break;
+        }
+    }
+    Ok(body)
+}
+
+fn read_request(stream: impl Read) -> Result<MockRequest> {
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut request_line = request_line.split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+
+    let mut content_length = 0;
+    let mut chunked = false;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse()?;
+            }
+            if name.eq_ignore_ascii_case("transfer-encoding")
+                && value.to_ascii_lowercase().contains("chunked")
+            {
+                chunked = true;
+            }
+        }
+    }
+
+    let body = if chunked {
+        read_chunked_body(&mut reader)?
+    } else {
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body)?;
+        body
+    };
+
+    Ok(MockRequest { method, path, body })
+}
+
+fn log_request(request: &MockRequest) {
+    info!(
+        "[mock api] {} {}",
+        request.method,
+        console::style(&request.path).bold()
+    );
+    match serde_json::from_slice::<Value>(&request.body) {
+        Ok(body) => info!("{}", serde_json::to_string_pretty(&body).unwrap()),
+        Err(_) if !request.body.is_empty() => info!("<{} bytes>", request.body.len()),
+        Err(_) => {}
+    }
+}
+
+impl MockApiServer {
+    pub fn start() -> Result<Self> {
+        let listener =
+            TcpListener::bind("127.0.0.1:0").context("Failed to start the mock API server")?;
+        let base_url = format!("http://{}", listener.local_addr()?);
+        debug!("Mock API server listening on {}", base_url);
+
+        let archive_url = format!("{}/archive", base_url);
+        let run_url = format!("{}/runs/{}", base_url, MOCK_RUN_ID);
+        thread::spawn(move || {
+            for stream in listener.incoming().filter_map(|stream| stream.ok()) {
+                if let Err(e) = handle_connection(stream, &archive_url, &run_url) {
+                    warn!("[mock api] Failed to handle request: {}", e);
+                }
+            }
+        });
+
+        Ok(Self { base_url })
+    }
+
+    pub fn graphql_url(&self) -> String {
+        format!("{}/", self.base_url)
+    }
+
+    pub fn upload_url(&self) -> String {
+        format!("{}/upload", self.base_url)
+    }
+}
+
+fn get_response_body(request: &MockRequest, archive_url: &str, run_url: &str) -> (u16, Value) {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/upload") => (
+            200,
+            json!({
+                "status": "success",
+                "uploadUrl": archive_url,
+                "runId": MOCK_RUN_ID,
+            }),
+        ),
+        ("PUT", "/archive") => (200, json!({})),
+        ("POST", "/") => {
+            let query = serde_json::from_slice::<Value>(&request.body)
+                .ok()
+                .and_then(|body| body["query"].as_str().map(|query| query.to_string()))
+                .unwrap_or_default();
+            if query.contains("FetchLocalRunReport") {
+                (
+                    200,
+                    json!({
+                        "data": {
+                            "repository": {
+                                "settings": { "allowedRegression": 0.1 },
+                                "runs": [{
+                                    "id": MOCK_RUN_ID,
+                                    "status": "COMPLETED",
+                                    "url": run_url,
+                                    "headReports": [{
+                                        "id": "mock-report-id",
+                                        "impact": null,
+                                        "conclusion": "Success",
+                                    }],
+                                }],
+                            }
+                        }
+                    }),
+                )
+            } else {
+                (
+                    200,
+                    json!({ "errors": [{ "message": "Operation not supported by the mock API" }] }),
+                )
+            }
+        }
+        _ => (404, json!({ "error": "Not found" })),
+    }
+}
+
+fn handle_connection(mut stream: TcpStream, archive_url: &str, run_url: &str) -> Result<()> {
+    let request = read_request(&stream)?;
+    log_request(&request);
+
+    let (status, body) = get_response_body(&request, archive_url, run_url);
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        if status == 200 { "OK" } else { "Not Found" },
+        body.len(),
+        body
+    )?;
+    stream.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, path: &str, body: &str) -> MockRequest {
+        MockRequest {
+            method: method.into(),
+            path: path.into(),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_read_request() {
+        let request =
+            read_request("PUT /archive HTTP/1.1\r\nContent-Length: 7\r\n\r\nprofile".as_bytes())
+                .unwrap();
+        assert_eq!(request.method, "PUT");
+        assert_eq!(request.path, "/archive");
+        assert_eq!(request.body, b"profile");
+    }
+
+    #[test]
+    fn test_read_chunked_request() {
+        let request = read_request(
+            "PUT /archive HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nprof\r\n3;ext=1\r\nile\r\n0\r\n\r\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(request.body, b"profile");
+    }
+
+    #[test]
+    fn test_upload_response() {
+        let (status, body) = get_response_body(
+            &request("POST", "/upload", "{}"),
+            "http://mock/archive",
+            "http://mock/run",
+        );
+        assert_eq!(status, 200);
+        assert_eq!(body["uploadUrl"], "http://mock/archive");
+        assert_eq!(body["runId"], MOCK_RUN_ID);
+    }
+
+    #[test]
+    fn test_run_report_response() {
+        let (status, body) = get_response_body(
+            &request(
+                "POST",
+                "/",
+                r#"{"query": "query FetchLocalRunReport { repository { id } }"}"#,
+            ),
+            "http://mock/archive",
+            "http://mock/run",
+        );
+        assert_eq!(status, 200);
+        assert_eq!(body["data"]["repository"]["runs"][0]["status"], "COMPLETED");
+    }
+
+    #[test]
+    fn test_unknown_route_response() {
+        let (status, _) = get_response_body(
+            &request("GET", "/unknown", ""),
+            "http://mock/archive",
+            "http://mock/run",
+        );
+        assert_eq!(status, 404);
+    }
+}
@@ -1,19 +1,216 @@
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures_util::stream;
 use lazy_static::lazy_static;
-use reqwest::ClientBuilder;
-use reqwest_middleware::{ClientBuilder as ClientWithMiddlewareBuilder, ClientWithMiddleware};
+use reqwest::{Body, Certificate, ClientBuilder, IntoUrl, Response, StatusCode};
+use reqwest_middleware::{
+    ClientBuilder as ClientWithMiddlewareBuilder, ClientWithMiddleware, RequestBuilder,
+};
 use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
+use tokio::time::{sleep, sleep_until, Instant};
+
+use crate::prelude::*;
 
 const UPLOAD_RETRY_COUNT: u32 = 3;
+const THROTTLED_CHUNK_SIZE: usize = 16 * 1024;
+const UPLOAD_ATTEMPTS: u32 = 4;
+const UPLOAD_RETRY_BASE_DELAY: Duration = Duration::from_secs(5);
+
+/// Certificate authorities trusted in addition to the system ones, set from `--ca-bundle`
+static CA_CERTIFICATES: OnceLock<Vec<Certificate>> = OnceLock::new();
+
+/// Get a client builder trusting the certificate authorities of `--ca-bundle`, used for every
+/// client of the runner
+pub fn client_builder() -> ClientBuilder {
+    let mut builder = ClientBuilder::new().user_agent("codspeed-runner");
+    for certificate in CA_CERTIFICATES.get().into_iter().flatten() {
+        builder = builder.add_root_certificate(certificate.clone());
+    }
+    builder
+}
+
+fn build_client() -> reqwest::Client {
+    client_builder().build().unwrap()
+}
 
 lazy_static! {
     pub static ref REQUEST_CLIENT: ClientWithMiddleware = ClientWithMiddlewareBuilder::new(
-        ClientBuilder::new()
-            .user_agent("codspeed-runner")
-            .build()
-            .unwrap()
+        build_client()
     )
     .with(RetryTransientMiddleware::new_with_policy(
         ExponentialBackoff::builder().build_with_max_retries(UPLOAD_RETRY_COUNT)
     ))
     .build();
+    /// Client without the retry middleware, for the uploads which are retried by
+    /// [`send_upload_with_retry`] since the requests with a streamed body cannot be retried by the
+    /// middleware
+    static ref UPLOAD_REQUEST_CLIENT: ClientWithMiddleware =
+        ClientWithMiddlewareBuilder::new(build_client()).build();
+}
+
+/// Delay from the start of a throttled upload before sending the byte at `offset`
+fn get_throttle_delay(offset: usize, bytes_per_second: u64) -> Duration {
+    Duration::from_secs_f64(offset as f64 / bytes_per_second as f64)
+}
+
+/// Body streaming the content in chunks, at most at `bytes_per_second`
+fn throttled_body(content: Bytes, bytes_per_second: u64) -> Body {
+    let start = Instant::now();
+    Body::wrap_stream(stream::unfold(
+        (content, 0),
+        move |(content, offset)| async move {
+            if offset >= content.len() {
+                return None;
+            }
+            sleep_until(start + get_throttle_delay(offset, bytes_per_second)).await;
+            let end = (offset + THROTTLED_CHUNK_SIZE).min(content.len());
+            let chunk = content.slice(offset..end);
+            Some((Ok::<_, std::io::Error>(chunk), (content, end)))
+        },
+    ))
+}
+
+/// Build a PUT request uploading the content, throttled to `rate_limit` bytes per second if any,
+/// to avoid saturating the uplink of shared runners. The request is not retried by the client,
+/// send it with [`send_upload_with_retry`].
+pub fn put_upload<U: IntoUrl>(url: U, content: Bytes, rate_limit: Option<u64>) -> RequestBuilder {
+    let request = UPLOAD_REQUEST_CLIENT.put(url);
+    match rate_limit {
+        Some(bytes_per_second) => request.body(throttled_body(content, bytes_per_second)),
+        None => request.body(content),
+    }
+}
+
+/// Delay before retrying a failed upload, doubling after each attempt
+fn get_upload_retry_delay(attempt: u32) -> Duration {
+    UPLOAD_RETRY_BASE_DELAY * 2u32.pow(attempt - 1)
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+/// Whether the request failed because of the connection, rather than because of the request itself
+fn is_retryable_error(error: &reqwest_middleware::Error) -> bool {
+    match error {
+        reqwest_middleware::Error::Reqwest(error) => {
+            error.is_connect() || error.is_timeout() || error.is_request()
+        }
+        reqwest_middleware::Error::Middleware(_) => false,
+    }
+}
+
+/// Send an upload request built by `build_request`, retrying with an exponential backoff on the
+/// connection errors and the transient server errors, since large uploads are more likely to be
+/// interrupted by a flaky network than the other requests. The response of the last attempt is
+/// returned, whatever its status.
+pub async fn send_upload_with_retry(
+    build_request: impl Fn() -> RequestBuilder,
+) -> Result<Response> {
+    let mut attempt = 1;
+    loop {
+        let error = match build_request().send().await {
+            Ok(response) if attempt < UPLOAD_ATTEMPTS && is_retryable_status(response.status()) => {
+                anyhow!(
+                    "{} {}",
+                    response.status(),
+                    response.text().await.unwrap_or_default()
+                )
+            }
+            Ok(response) => return Ok(response),
+            Err(error) if attempt < UPLOAD_ATTEMPTS && is_retryable_error(&error) => anyhow!(error),
+            Err(error) if attempt > 1 => {
+                return Err(
+                    anyhow!(error).context(format!("Upload failed after {} attempts", attempt))
+                )
+            }
+            Err(error) => return Err(error.into()),
+        };
+        let delay = get_upload_retry_delay(attempt);
+        warn!(
+            "Upload failed: {}, retrying in {} seconds",
+            error,
+            delay.as_secs()
+        );
+        sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// Trust the certificate authorities of a PEM bundle in the clients of the runner, in addition to
+/// the system ones. It must be called before any client is built.
+///
+/// The bundle is not exported as `SSL_CERT_FILE`, which would be inherited by the benchmark
+/// processes and replace their system certificate store.
+pub fn set_ca_bundle(ca_bundle: &Path) -> Result<()> {
+    let pem_bundle = fs::read(ca_bundle).context(format!(
+        "Failed to read the CA bundle at {}",
+        ca_bundle.display()
+    ))?;
+    let certificates = Certificate::from_pem_bundle(&pem_bundle).context(format!(
+        "Failed to parse the CA bundle at {}",
+        ca_bundle.display()
+    ))?;
+    if certificates.is_empty() {
+        bail!(
+            "No certificate found in the CA bundle at {}",
+            ca_bundle.display()
+        );
+    }
+    debug!(
+        "Using {} certificate authorities from {}",
+        certificates.len(),
+        ca_bundle.display()
+    );
+    if CA_CERTIFICATES.set(certificates).is_err() {
+        bail!("The CA bundle is already set");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_throttle_delay() {
+        assert_eq!(get_throttle_delay(0, 1000), Duration::ZERO);
+        assert_eq!(get_throttle_delay(2500, 1000), Duration::from_millis(2500));
+        assert_eq!(
+            get_throttle_delay(10 * 1024 * 1024, 10 * 1024 * 1024),
+            Duration::from_secs(1)
+        );
+    }
+
+    #[test]
+    fn test_get_upload_retry_delay() {
+        assert_eq!(get_upload_retry_delay(1), Duration::from_secs(5));
+        assert_eq!(get_upload_retry_delay(2), Duration::from_secs(10));
+        assert_eq!(get_upload_retry_delay(3), Duration::from_secs(20));
+    }
+
+    #[test]
+    fn test_is_retryable_status() {
+        assert!(is_retryable_status(StatusCode::BAD_GATEWAY));
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_retryable_status(StatusCode::FORBIDDEN));
+        assert!(!is_retryable_status(StatusCode::BAD_REQUEST));
+    }
+
+    #[test]
+    fn test_set_ca_bundle_without_certificates() {
+        let ca_bundle = tempfile::NamedTempFile::new().unwrap();
+        fs::write(ca_bundle.path(), "not a certificate\n").unwrap();
+        assert_eq!(
+            set_ca_bundle(ca_bundle.path()).unwrap_err().to_string(),
+            format!(
+                "No certificate found in the CA bundle at {}",
+                ca_bundle.path().display()
+            )
+        );
+    }
 }
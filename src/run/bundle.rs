@@ -0,0 +1,325 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use tokio::fs::File;
+use tokio_tar::{Archive, Builder, Header};
+use url::Url;
+
+use crate::api_client::CodSpeedAPIClient;
+use crate::config::CodSpeedConfig;
+use crate::prelude::*;
+use crate::run::{
+    config::DEFAULT_UPLOAD_URL,
+    helpers::parse_rate,
+    poll_results,
+    uploader::{
+        self, ArchiveSettings, ProfileArchive, ProfileCompression, RunGroup, UploadMetadata,
+        ARCHIVE_SETTINGS_FILE_NAME, UPLOAD_METADATA_FILE_NAME,
+    },
+};
+
+#[derive(Args, Debug)]
+pub struct UploadArgs {
+    /// The bundle written by `codspeed run --output`
+    #[arg(required_unless_present = "profile_folder")]
+    pub bundle: Option<PathBuf>,
+
+    /// Upload the profile folder of a run instead of a bundle, e.g. after a failed upload
+    #[arg(long, conflicts_with = "bundle")]
+    pub profile_folder: Option<PathBuf>,
+
+    /// The upload URL to use for uploading the results, useful for on-premises installations
+    #[arg(long)]
+    pub upload_url: Option<String>,
+
+    /// The token to use for uploading the results
+    #[arg(long, env = "CODSPEED_TOKEN")]
+    pub token: Option<String>,
+
+    /// Maximum upload bandwidth, e.g. `10MB/s`, to avoid saturating the uplink of shared
+    /// self-hosted runners
+    #[arg(long, env = "CODSPEED_UPLOAD_RATE_LIMIT")]
+    pub upload_rate_limit: Option<String>,
+}
+
+async fn append_file(builder: &mut Builder<File>, name: &str, content: &[u8]) -> Result<()> {
+    let mut header = Header::new_gnu();
+    header.set_size(content.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, name, content).await?;
+    Ok(())
+}
+
+/// Write the profile archive of a run and its upload metadata to a bundle, a tar archive to be
+/// uploaded later with `codspeed upload`, e.g. from a machine with network access
+pub async fn write(
+    bundle_path: &Path,
+    upload_metadata: &UploadMetadata,
+    archive: &ProfileArchive,
+) -> Result<()> {
+    let file = File::create(bundle_path).await.context(format!(
+        "Failed to create the bundle at {}",
+        bundle_path.display()
+    ))?;
+    let mut builder = Builder::new(file);
+    append_file(
+        &mut builder,
+        UPLOAD_METADATA_FILE_NAME,
+        &serde_json::to_vec_pretty(upload_metadata)?,
+    )
+    .await?;
+    append_file(
+        &mut builder,
+        archive.encoding.archive_file_name(),
+        &archive.buffer,
+    )
+    .await?;
+    builder.finish().await?;
+    info!("Bundle written to {}", bundle_path.display());
+    Ok(())
+}
+
+async fn read(bundle_path: &Path) -> Result<(UploadMetadata, ProfileArchive)> {
+    let file = File::open(bundle_path).await.context(format!(
+        "Failed to open the bundle at {}",
+        bundle_path.display()
+    ))?;
+    let bundle_dir = tempfile::tempdir()?;
+    Archive::new(file)
+        .unpack(bundle_dir.path())
+        .await
+        .context("Failed to extract the bundle")?;
+
+    let upload_metadata = fs::read(bundle_dir.path().join(UPLOAD_METADATA_FILE_NAME))
+        .context("Invalid bundle, the upload metadata is missing")?;
+    let upload_metadata: UploadMetadata = serde_json::from_slice(&upload_metadata)
+        .context("Invalid bundle, failed to parse the upload metadata")?;
+    let archive = ProfileArchive::from_buffer(
+        fs::read(
+            bundle_dir
+                .path()
+                .join(upload_metadata.profile_encoding.archive_file_name()),
+        )
+        .context("Invalid bundle, the profile archive is missing")?,
+        upload_metadata.profile_encoding,
+    );
+    if archive.hash != upload_metadata.profile_md5
+        || upload_metadata
+            .profile_sha256
+            .as_ref()
+            .is_some_and(|sha256| *sha256 != archive.sha256)
+    {
+        bail!("Invalid bundle, the profile archive does not match its upload metadata");
+    }
+    archive.verify().await?;
+    Ok((upload_metadata, archive))
+}
+
+/// Read the upload metadata persisted in the profile folder of a run, and archive the folder again
+/// with the persisted archive settings. The profile folders of older versions of the runner have
+/// no archive settings, they are archived with the default ones.
+async fn read_profile_folder(profile_folder: &Path) -> Result<(UploadMetadata, ProfileArchive)> {
+    let upload_metadata =
+        fs::read(profile_folder.join(UPLOAD_METADATA_FILE_NAME)).context(format!(
+            "No upload metadata in {}, is it the profile folder of a run?",
+            profile_folder.display()
+        ))?;
+    let mut upload_metadata: UploadMetadata = serde_json::from_slice(&upload_metadata)
+        .context("Failed to parse the upload metadata of the profile folder")?;
+    let archive_settings_path = profile_folder.join(ARCHIVE_SETTINGS_FILE_NAME);
+    let archive_settings = if archive_settings_path.exists() {
+        serde_json::from_slice(&fs::read(&archive_settings_path)?)
+            .context("Failed to parse the archive settings of the profile folder")?
+    } else {
+        ArchiveSettings {
+            compression: ProfileCompression {
+                encoding: upload_metadata.profile_encoding,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    };
+    let archive = uploader::create_profile_archive(profile_folder, &archive_settings).await?;
+    archive.verify().await?;
+    upload_metadata.profile_md5 = archive.hash.clone();
+    upload_metadata.profile_sha256 = Some(archive.sha256.clone());
+    Ok((upload_metadata, archive))
+}
+
+/// Upload a bundle written by `codspeed run --output`, or the profile folder of a run
+pub async fn upload(args: UploadArgs, api_client: &CodSpeedAPIClient) -> Result<()> {
+    let (mut upload_metadata, archive) = match (&args.bundle, &args.profile_folder) {
+        (_, Some(profile_folder)) => read_profile_folder(profile_folder).await?,
+        (Some(bundle), None) => read(bundle).await?,
+        (None, None) => bail!("Either a bundle or a --profile-folder is required"),
+    };
+    let raw_upload_url = args.upload_url.unwrap_or_else(|| DEFAULT_UPLOAD_URL.into());
+    let upload_url = Url::parse(&raw_upload_url)
+        .map_err(|e| anyhow!("Invalid upload URL: {}, {}", raw_upload_url, e))?;
+
+    let upload_rate_limit = args
+        .upload_rate_limit
+        .as_deref()
+        .map(parse_rate)
+        .transpose()?;
+
+    let is_local = upload_metadata.platform == "local";
+    let token = match args.token {
+        None if is_local => CodSpeedConfig::load()?.auth.token,
+        token => token,
+    };
+    if is_local && token.is_none() {
+        bail!("You have to authenticate the CLI first. Run `codspeed auth login`.");
+    }
+    upload_metadata.tokenless = token.is_none();
+
+    start_group!("Uploading performance data");
+    let upload_result = uploader::upload_archive(
+        &upload_url,
+        token.as_ref(),
+        &upload_metadata,
+        archive,
+        upload_rate_limit,
+    )
+    .await?;
+    end_group!();
+
+    let is_intermediate_shard = upload_metadata
+        .run_group
+        .as_ref()
+        .is_some_and(RunGroup::is_intermediate_shard);
+    if is_local && is_intermediate_shard {
+        info!("The results of the run group are fetched by the upload of its last shard");
+    } else if is_local {
+        start_group!("Fetching the results");
+        poll_results::poll_results(
+            api_client,
+            &upload_metadata.ci_provider_metadata,
+            upload_result.run_id,
+        )
+        .await?;
+        end_group!();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::run::uploader::{ProfileEncoding, Redaction};
+
+    #[tokio::test]
+    async fn test_write_and_read() {
+        let profile_folder = tempfile::tempdir().unwrap();
+        fs::write(profile_folder.path().join("1234.out"), "data").unwrap();
+        let archive = ProfileArchive::create(
+            profile_folder.path(),
+            &ProfileCompression {
+                encoding: ProfileEncoding::Zstd,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        let upload_metadata = UploadMetadata {
+            profile_md5: archive.hash.clone(),
+            profile_encoding: archive.encoding,
+            profile_sha256: Some(archive.sha256.clone()),
+            ..UploadMetadata::test()
+        };
+
+        let bundle_dir = tempfile::tempdir().unwrap();
+        let bundle_path = bundle_dir.path().join("bundle.tar");
+        write(&bundle_path, &upload_metadata, &archive)
+            .await
+            .unwrap();
+
+        let (read_metadata, read_archive) = read(&bundle_path).await.unwrap();
+        assert_eq!(read_metadata.get_hash(), upload_metadata.get_hash());
+        assert_eq!(read_archive.buffer, archive.buffer);
+    }
+
+    #[tokio::test]
+    async fn test_read_mismatching_archive() {
+        let bundle_dir = tempfile::tempdir().unwrap();
+        let bundle_path = bundle_dir.path().join("bundle.tar");
+        write(
+            &bundle_path,
+            &UploadMetadata::test(),
+            &ProfileArchive::from_buffer(b"profile".to_vec(), ProfileEncoding::Gzip),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            read(&bundle_path).await.err().unwrap().to_string(),
+            "Invalid bundle, the profile archive does not match its upload metadata"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_profile_folder() {
+        let profile_folder = tempfile::tempdir().unwrap();
+        fs::write(profile_folder.path().join("1234.out"), "data").unwrap();
+        fs::write(
+            profile_folder.path().join(UPLOAD_METADATA_FILE_NAME),
+            serde_json::to_vec(&UploadMetadata::test()).unwrap(),
+        )
+        .unwrap();
+
+        let (upload_metadata, archive) = read_profile_folder(profile_folder.path()).await.unwrap();
+        assert_eq!(upload_metadata.profile_md5, archive.hash);
+        assert_eq!(upload_metadata.profile_sha256, Some(archive.sha256.clone()));
+        archive.verify().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_read_profile_folder_with_archive_settings() {
+        let profile_folder = tempfile::tempdir().unwrap();
+        fs::write(profile_folder.path().join("1234.out"), "data").unwrap();
+        fs::write(
+            profile_folder.path().join("perf-1234.map"),
+            "7f1c 20 /home/alice/main",
+        )
+        .unwrap();
+        fs::write(
+            profile_folder.path().join(UPLOAD_METADATA_FILE_NAME),
+            serde_json::to_vec(&UploadMetadata::test()).unwrap(),
+        )
+        .unwrap();
+        fs::write(
+            profile_folder.path().join(ARCHIVE_SETTINGS_FILE_NAME),
+            serde_json::to_vec(&ArchiveSettings {
+                compression: ProfileCompression {
+                    encoding: ProfileEncoding::Zstd,
+                    level: Some(19),
+                    threads: Some(2),
+                },
+                max_size: Some(10_000),
+                redaction: Redaction {
+                    path_prefixes: vec!["/home/alice/".into()],
+                    ..Default::default()
+                },
+            })
+            .unwrap(),
+        )
+        .unwrap();
+
+        let (_, archive) = read_profile_folder(profile_folder.path()).await.unwrap();
+        assert_eq!(archive.encoding, ProfileEncoding::Zstd);
+        let manifest = archive.manifest.unwrap();
+        let perf_map = manifest
+            .files
+            .iter()
+            .find(|file| file.path == "perf-1234.map")
+            .unwrap();
+        assert_eq!(perf_map.size, "7f1c 20 main".len() as u64);
+        assert!(!manifest
+            .files
+            .iter()
+            .any(|file| file.path == ARCHIVE_SETTINGS_FILE_NAME));
+    }
+}
@@ -6,6 +6,8 @@ use std::collections::HashSet;
 use sysinfo::{CpuRefreshKind, MemoryRefreshKind, RefreshKind, System};
 
 use crate::prelude::*;
+use crate::run::config::Config;
+use crate::run::runner::ExecutorName;
 
 fn get_user() -> Result<String> {
     let user_output = Command::new("whoami")
@@ -19,6 +21,15 @@ fn get_user() -> Result<String> {
     Ok(output_str.trim().to_string())
 }
 
+/// CPU settings applied by the system tuning for the duration of the run
+#[derive(Eq, PartialEq, Hash, Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CpuTuning {
+    pub governor: Option<String>,
+    pub turbo_disabled: bool,
+    pub smt_disabled: bool,
+}
+
 #[derive(Eq, PartialEq, Hash, Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct SystemInfo {
@@ -32,6 +43,8 @@ pub struct SystemInfo {
     pub cpu_vendor_id: String,
     pub cpu_cores: usize,
     pub total_memory_gb: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_tuning: Option<CpuTuning>,
 }
 
 #[cfg(test)]
@@ -48,6 +61,7 @@ impl SystemInfo {
             cpu_vendor_id: "GenuineIntel".to_string(),
             cpu_cores: 2,
             total_memory_gb: 8,
+            cpu_tuning: None,
         }
     }
 }
@@ -91,6 +105,7 @@ impl SystemInfo {
             cpu_vendor_id,
             cpu_cores,
             total_memory_gb,
+            cpu_tuning: None,
         })
     }
 }
@@ -108,6 +123,33 @@ lazy_static! {
     };
 }
 
+/// Options relying on Linux-only tools or interfaces
+fn get_linux_only_options(config: &Config) -> Vec<&'static str> {
+    let mut options = vec![];
+    if config.cpu_affinity.is_some() {
+        options.push("--cpu-affinity");
+    }
+    if config.tune_system {
+        options.push("--tune-system");
+    }
+    if config.disable_aslr {
+        options.push("--disable-aslr");
+    }
+    if config.resource_limits.is_some() {
+        options.push("--cpu-limit/--memory-limit");
+    }
+    if config.realtime_priority.is_some() {
+        options.push("--realtime-priority");
+    }
+    if config.cgroup_accounting {
+        options.push("--cgroup-accounting");
+    }
+    if config.hang_timeout.is_some() {
+        options.push("--hang-timeout");
+    }
+    options
+}
+
 /// Checks if the provided system info is supported
 ///
 /// Supported systems:
@@ -115,9 +157,28 @@ lazy_static! {
 /// - Ubuntu 22.04 x86_64 and aarch64
 /// - Debian 11 x86_64
 /// - Debian 12 x86_64
-pub fn check_system(system_info: &SystemInfo) -> Result<()> {
+/// - macOS, with the walltime executor only
+pub fn check_system(
+    system_info: &SystemInfo,
+    executor_name: &ExecutorName,
+    config: &Config,
+) -> Result<()> {
     debug!("System info: {:#?}", system_info);
 
+    if system_info.os == "macos" {
+        if *executor_name != ExecutorName::WallTime {
+            bail!("Only the walltime executor is supported on macOS, set CODSPEED_RUNNER_MODE=walltime");
+        }
+        let linux_only_options = get_linux_only_options(config);
+        if !linux_only_options.is_empty() {
+            bail!(
+                "The following options are not supported on macOS: {}",
+                linux_only_options.join(", ")
+            );
+        }
+        return Ok(());
+    }
+
     let system_tuple = (
         system_info.os.as_str(),
         system_info.os_version.as_str(),
@@ -130,3 +191,43 @@ pub fn check_system(system_info: &SystemInfo) -> Result<()> {
 
     bail!("Unsupported system: {:?}", system_info);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn macos_system_info() -> SystemInfo {
+        SystemInfo {
+            os: "macos".to_string(),
+            os_version: "14.5".to_string(),
+            arch: "aarch64".to_string(),
+            ..SystemInfo::test()
+        }
+    }
+
+    #[test]
+    fn test_check_system_linux() {
+        let config = Config::test();
+        assert!(check_system(&SystemInfo::test(), &ExecutorName::Valgrind, &config).is_ok());
+        assert!(check_system(&SystemInfo::test(), &ExecutorName::WallTime, &config).is_ok());
+    }
+
+    #[test]
+    fn test_check_system_macos() {
+        let config = Config::test();
+        assert!(check_system(&macos_system_info(), &ExecutorName::WallTime, &config).is_ok());
+        assert!(check_system(&macos_system_info(), &ExecutorName::Valgrind, &config).is_err());
+
+        let config = Config {
+            disable_aslr: true,
+            cpu_affinity: Some(vec![0]),
+            ..Config::test()
+        };
+        assert_eq!(
+            check_system(&macos_system_info(), &ExecutorName::WallTime, &config)
+                .unwrap_err()
+                .to_string(),
+            "The following options are not supported on macOS: --cpu-affinity, --disable-aslr"
+        );
+    }
+}
@@ -140,6 +140,7 @@ impl CIProvider for BuildkiteProvider {
             repository: self.repository.clone(),
             ref_: self.ref_.clone(),
             repository_root_path: self.repository_root_path.clone(),
+            run_url: None,
             gh_data: None,
             gl_data: None,
             sender: None,
@@ -144,6 +144,7 @@ impl CIProvider for GitHubActionsProvider {
             repository: self.repository.clone(),
             ref_: self.ref_.clone(),
             repository_root_path: self.repository_root_path.clone(),
+            run_url: None,
         })
     }
 }
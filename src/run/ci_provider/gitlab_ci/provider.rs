@@ -164,6 +164,7 @@ impl CIProvider for GitLabCIProvider {
             repository: self.repository.clone(),
             ref_: self.ref_.clone(),
             repository_root_path: self.repository_root_path.clone(),
+            run_url: None,
         })
     }
 }
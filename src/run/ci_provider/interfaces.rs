@@ -21,6 +21,9 @@ pub struct CIProviderMetadata {
     pub gh_data: Option<GhData>,
     pub gl_data: Option<GlData>,
     pub repository_root_path: String,
+    /// URL of the CI run, only known when provided by a CI metadata file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub run_url: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
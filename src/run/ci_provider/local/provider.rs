@@ -51,6 +51,34 @@ fn extract_provider_owner_and_repository_from_remote_url(
     ))
 }
 
+/// Get the URL of the `origin` remote, or of the only remote of the repository
+fn get_remote_url(git_repository: &Repository) -> Result<String> {
+    if let Ok(remote) = git_repository.find_remote("origin") {
+        return remote
+            .url()
+            .map(|url| url.to_string())
+            .context("The origin remote URL is not valid UTF-8");
+    }
+
+    let remotes = git_repository.remotes()?;
+    let remote_names = remotes.iter().flatten().collect_vec();
+    match remote_names.as_slice() {
+        [remote_name] => {
+            warn!("No origin remote found, using the {} remote", remote_name);
+            let remote = git_repository.find_remote(remote_name)?;
+            remote
+                .url()
+                .map(|url| url.to_string())
+                .context(format!("The {} remote URL is not valid UTF-8", remote_name))
+        }
+        [] => bail!("No remote found in the repository, add the remote of the repository on GitHub or GitLab as origin"),
+        _ => bail!(
+            "No origin remote found in the repository and several other remotes exist ({}), set the remote of the repository on GitHub or GitLab as origin",
+            remote_names.join(", ")
+        ),
+    }
+}
+
 impl TryFrom<&Config> for LocalProvider {
     type Error = Error;
     fn try_from(_config: &Config) -> Result<Self> {
@@ -68,9 +96,9 @@ impl TryFrom<&Config> for LocalProvider {
             repository_root_path
         ))?;
 
-        let remote = git_repository.find_remote("origin")?;
+        let remote_url = get_remote_url(&git_repository)?;
         let (repository_provider, owner, repository) =
-            extract_provider_owner_and_repository_from_remote_url(remote.url().unwrap())?;
+            extract_provider_owner_and_repository_from_remote_url(&remote_url)?;
 
         let head = git_repository.head().context("Failed to get HEAD")?;
         let ref_ = head
@@ -82,6 +110,10 @@ impl TryFrom<&Config> for LocalProvider {
             let branch = head.shorthand().context("Failed to get HEAD branch name")?;
             Some(branch.to_string())
         } else {
+            warn!(
+                "HEAD is detached at {}, the run will not be associated with a branch",
+                &ref_[..7]
+            );
             None
         };
 
@@ -133,6 +165,7 @@ impl CIProvider for LocalProvider {
             repository: self.repository.clone(),
             ref_: self.ref_.clone(),
             repository_root_path: self.repository_root_path.clone(),
+            run_url: None,
         })
     }
 }
@@ -183,6 +216,37 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_get_remote_url() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let git_repository = Repository::init(tmp_dir.path()).unwrap();
+        assert_eq!(
+            get_remote_url(&git_repository).unwrap_err().to_string(),
+            "No remote found in the repository, add the remote of the repository on GitHub or GitLab as origin"
+        );
+
+        git_repository
+            .remote("upstream", "git@github.com:CodSpeedHQ/runner.git")
+            .unwrap();
+        assert_eq!(
+            get_remote_url(&git_repository).unwrap(),
+            "git@github.com:CodSpeedHQ/runner.git"
+        );
+
+        git_repository
+            .remote("fork", "git@github.com:octocat/runner.git")
+            .unwrap();
+        assert!(get_remote_url(&git_repository).is_err());
+
+        git_repository
+            .remote("origin", "https://github.com/CodSpeedHQ/runner.git")
+            .unwrap();
+        assert_eq!(
+            get_remote_url(&git_repository).unwrap(),
+            "https://github.com/CodSpeedHQ/runner.git"
+        );
+    }
+
     // TODO: uncomment later when we have a way to mock git repository
     // #[test]
     // fn test_provider_metadata() {
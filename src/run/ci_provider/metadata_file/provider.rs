@@ -0,0 +1,222 @@
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+use simplelog::SharedLogger;
+
+use crate::local_logger::get_local_logger;
+use crate::prelude::*;
+use crate::run::{
+    ci_provider::{
+        interfaces::{CIProviderMetadata, RepositoryProvider, RunEvent, Sender},
+        provider::CIProvider,
+    },
+    config::Config,
+    helpers::find_repository_root,
+};
+
+/// Content of the file passed with `--ci-metadata-file`
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct CIMetadataFile {
+    #[serde(default = "default_repository_provider")]
+    repository_provider: RepositoryProvider,
+    owner: String,
+    repository: String,
+    commit_hash: String,
+    #[serde(rename = "ref")]
+    ref_: String,
+    head_ref: Option<String>,
+    base_ref: Option<String>,
+    event: RunEvent,
+    sender: Option<Sender>,
+    run_url: Option<String>,
+}
+
+fn default_repository_provider() -> RepositoryProvider {
+    RepositoryProvider::GitHub
+}
+
+/// A provider reading the whole CI metadata from a file instead of detecting it from the
+/// environment, for CI systems that are not supported natively.
+#[derive(Debug)]
+pub struct MetadataFileProvider {
+    metadata: CIMetadataFile,
+    repository_root_path: String,
+}
+
+fn read_metadata_file(path: &Path) -> Result<CIMetadataFile> {
+    let content = fs::read_to_string(path).context(format!(
+        "Failed to read the CI metadata file at {}",
+        path.display()
+    ))?;
+    serde_json::from_str(&content).context(format!(
+        "Failed to parse the CI metadata file at {}",
+        path.display()
+    ))
+}
+
+impl TryFrom<&Config> for MetadataFileProvider {
+    type Error = Error;
+    fn try_from(config: &Config) -> Result<Self> {
+        let Some(ci_metadata_file) = &config.ci_metadata_file else {
+            bail!("No CI metadata file provided");
+        };
+        if config.token.is_none() {
+            bail!("Token authentication is required when using a CI metadata file");
+        }
+
+        let metadata = read_metadata_file(ci_metadata_file)?;
+        if metadata.commit_hash.len() != 40
+            || !metadata.commit_hash.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            bail!(
+                "Invalid commit hash in the CI metadata file: {}, expected a full 40 characters hash",
+                metadata.commit_hash
+            );
+        }
+
+        let mut repository_root_path =
+            find_repository_root(&std::env::current_dir()?).unwrap_or(std::env::current_dir()?);
+        // Add a trailing slash to the path
+        repository_root_path.push("");
+
+        Ok(Self {
+            metadata,
+            repository_root_path: repository_root_path.to_string_lossy().to_string(),
+        })
+    }
+}
+
+impl CIProvider for MetadataFileProvider {
+    fn get_repository_provider(&self) -> RepositoryProvider {
+        self.metadata.repository_provider.clone()
+    }
+
+    fn get_logger(&self) -> Box<dyn SharedLogger> {
+        get_local_logger()
+    }
+
+    fn get_provider_name(&self) -> &'static str {
+        "CI metadata file"
+    }
+
+    /// A dedicated slug, the runs described by the file must not be reported as the ones of a
+    /// supported provider
+    fn get_provider_slug(&self) -> &'static str {
+        "ci-metadata-file"
+    }
+
+    fn get_commit_hash(&self, _repository_root_path: &str) -> Result<String> {
+        Ok(self.metadata.commit_hash.clone())
+    }
+
+    fn get_ci_provider_metadata(&self) -> Result<CIProviderMetadata> {
+        Ok(CIProviderMetadata {
+            base_ref: self.metadata.base_ref.clone(),
+            head_ref: self.metadata.head_ref.clone(),
+            event: self.metadata.event.clone(),
+            gh_data: None,
+            gl_data: None,
+            sender: self.metadata.sender.clone(),
+            owner: self.metadata.owner.clone(),
+            repository: self.metadata.repository.clone(),
+            ref_: self.metadata.ref_.clone(),
+            repository_root_path: self.repository_root_path.clone(),
+            run_url: self.metadata.run_url.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PULL_REQUEST_METADATA: &str = r#"{
+        "owner": "my-org",
+        "repository": "my-repo",
+        "commitHash": "5bd77cb0da72bef094893ed45fb793ff16ecfbe3",
+        "ref": "refs/pull/22/merge",
+        "headRef": "feat/my-feature",
+        "baseRef": "main",
+        "event": "pull_request",
+        "sender": { "id": "1234", "login": "octocat" },
+        "runUrl": "https://ci.example.com/builds/42"
+    }"#;
+
+    fn config_with_metadata_file(content: &str) -> (Config, tempfile::NamedTempFile) {
+        let metadata_file = tempfile::NamedTempFile::new().unwrap();
+        fs::write(metadata_file.path(), content).unwrap();
+        let config = Config {
+            token: Some("token".into()),
+            ci_metadata_file: Some(metadata_file.path().to_path_buf()),
+            ..Config::test()
+        };
+        (config, metadata_file)
+    }
+
+    #[test]
+    fn test_try_from_pull_request() {
+        let (config, _metadata_file) = config_with_metadata_file(PULL_REQUEST_METADATA);
+        let provider = MetadataFileProvider::try_from(&config).unwrap();
+        let provider_metadata = provider.get_ci_provider_metadata().unwrap();
+
+        assert_eq!(
+            provider.get_repository_provider(),
+            RepositoryProvider::GitHub
+        );
+        assert_eq!(provider.get_provider_slug(), "ci-metadata-file");
+        assert_eq!(
+            provider.get_commit_hash("").unwrap(),
+            "5bd77cb0da72bef094893ed45fb793ff16ecfbe3"
+        );
+        assert_eq!(provider_metadata.owner, "my-org");
+        assert_eq!(provider_metadata.repository, "my-repo");
+        assert_eq!(provider_metadata.ref_, "refs/pull/22/merge");
+        assert_eq!(provider_metadata.head_ref, Some("feat/my-feature".into()));
+        assert_eq!(provider_metadata.base_ref, Some("main".into()));
+        assert_eq!(provider_metadata.event, RunEvent::PullRequest);
+        assert_eq!(
+            provider_metadata.sender,
+            Some(Sender {
+                id: "1234".into(),
+                login: "octocat".into()
+            })
+        );
+        assert_eq!(
+            provider_metadata.run_url,
+            Some("https://ci.example.com/builds/42".into())
+        );
+    }
+
+    #[test]
+    fn test_try_from_invalid_commit_hash() {
+        let (config, _metadata_file) = config_with_metadata_file(
+            r#"{
+                "repositoryProvider": "GITLAB",
+                "owner": "my-org",
+                "repository": "my-repo",
+                "commitHash": "5bd77cb",
+                "ref": "refs/heads/main",
+                "event": "push"
+            }"#,
+        );
+        assert_eq!(
+            MetadataFileProvider::try_from(&config)
+                .unwrap_err()
+                .to_string(),
+            "Invalid commit hash in the CI metadata file: 5bd77cb, expected a full 40 characters hash"
+        );
+    }
+
+    #[test]
+    fn test_try_from_unknown_field() {
+        let (config, _metadata_file) = config_with_metadata_file(&PULL_REQUEST_METADATA.replace(
+            r#""owner": "my-org","#,
+            r#""owner": "my-org", "commit": "5bd77cb","#,
+        ));
+        let error = format!("{:#}", MetadataFileProvider::try_from(&config).unwrap_err());
+        assert!(error.starts_with("Failed to parse the CI metadata file"));
+        assert!(error.contains("unknown field `commit`"));
+    }
+}
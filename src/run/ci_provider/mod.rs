@@ -7,6 +7,7 @@ use buildkite::BuildkiteProvider;
 use github_actions::GitHubActionsProvider;
 use gitlab_ci::GitLabCIProvider;
 use local::LocalProvider;
+use metadata_file::MetadataFileProvider;
 use provider::CIProviderDetector;
 
 use crate::prelude::*;
@@ -18,8 +19,15 @@ pub use self::provider::CIProvider;
 mod buildkite;
 mod github_actions;
 mod local;
+mod metadata_file;
 
 pub fn get_provider(config: &Config) -> Result<Box<dyn CIProvider>> {
+    // An explicit metadata file takes precedence over the detection of the CI provider
+    if config.ci_metadata_file.is_some() {
+        let provider = MetadataFileProvider::try_from(config)?;
+        return Ok(Box::new(provider));
+    }
+
     if BuildkiteProvider::detect() {
         let provider = BuildkiteProvider::try_from(config)?;
         return Ok(Box::new(provider));
@@ -1,3 +1,5 @@
+use std::env;
+
 use git2::Repository;
 use simplelog::SharedLogger;
 
@@ -5,7 +7,7 @@ use crate::prelude::*;
 use crate::run::check_system::SystemInfo;
 use crate::run::config::Config;
 use crate::run::runner::ExecutorName;
-use crate::run::uploader::{Runner, UploadMetadata};
+use crate::run::uploader::{ProfileArchive, Runner, UploadMetadata};
 
 use super::interfaces::{CIProviderMetadata, RepositoryProvider};
 
@@ -14,6 +16,41 @@ pub trait CIProviderDetector {
     fn detect() -> bool;
 }
 
+/// Environment variables holding the commit being built, set by the supported CI providers
+const COMMIT_HASH_ENV_VARS: [&str; 3] = ["GITHUB_SHA", "CI_COMMIT_SHA", "BUILDKITE_COMMIT"];
+
+fn is_full_commit_hash(value: &str) -> bool {
+    value.len() == 40 && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Get the commit hash from the environment of the CI provider, if it is a full commit hash
+fn get_commit_hash_from_env() -> Option<(&'static str, String)> {
+    COMMIT_HASH_ENV_VARS.into_iter().find_map(|name| {
+        env::var(name)
+            .ok()
+            .filter(|value| is_full_commit_hash(value))
+            .map(|value| (name, value))
+    })
+}
+
+/// Get the commit hash from the repository, falling back on the environment of the CI provider
+/// when it cannot be read, e.g. when the checkout does not contain a usable `.git` directory
+fn get_commit_hash_or_env(repository_root_path: &str) -> Result<String> {
+    match get_commit_hash(repository_root_path) {
+        Ok(commit_hash) => Ok(commit_hash),
+        Err(e) => match get_commit_hash_from_env() {
+            Some((name, commit_hash)) => {
+                warn!(
+                    "{:#}, using the commit hash from {} instead: {}",
+                    e, name, commit_hash
+                );
+                Ok(commit_hash)
+            }
+            None => Err(e),
+        },
+    }
+}
+
 fn get_commit_hash(repository_root_path: &str) -> Result<String> {
     let repo = Repository::open(repository_root_path).context(format!(
         "Failed to open repository at path: {}",
@@ -57,6 +94,11 @@ pub trait CIProvider {
     /// ```
     fn get_provider_slug(&self) -> &'static str;
 
+    /// Returns the hash of the commit being benchmarked, read from the repository by default.
+    fn get_commit_hash(&self, repository_root_path: &str) -> Result<String> {
+        get_commit_hash_or_env(repository_root_path)
+    }
+
     /// Returns the metadata related to the CI provider.
     fn get_ci_provider_metadata(&self) -> Result<CIProviderMetadata>;
 
@@ -65,7 +107,7 @@ pub trait CIProvider {
     /// # Arguments
     ///
     /// * `config` - A reference to the configuration.
-    /// * `archive_hash` - The hash of the archive to be uploaded.
+    /// * `archive` - The profile archive to be uploaded.
     /// * `instruments` - A reference to the active instruments.
     ///
     /// # Example
@@ -74,31 +116,42 @@ pub trait CIProvider {
     /// let provider = MyCIProvider::new();
     /// let config = Config::new();
     /// let instruments = Instruments::new();
-    /// let metadata = provider.get_upload_metadata(&config, "abc123").unwrap();
+    /// let metadata = provider.get_upload_metadata(&config, &archive).unwrap();
     /// ```
     fn get_upload_metadata(
         &self,
         config: &Config,
         system_info: &SystemInfo,
-        archive_hash: &str,
+        archive: &ProfileArchive,
         executor_name: ExecutorName,
     ) -> Result<UploadMetadata> {
         let ci_provider_metadata = self.get_ci_provider_metadata()?;
 
-        let commit_hash = get_commit_hash(&ci_provider_metadata.repository_root_path)?;
+        let commit_hash = self.get_commit_hash(&ci_provider_metadata.repository_root_path)?;
+
+        // CPU pinning and resource limits are only applied by the walltime executor
+        let (cpu_affinity, resource_limits) = match executor_name {
+            ExecutorName::WallTime => (config.cpu_affinity.clone(), config.resource_limits.clone()),
+            ExecutorName::Valgrind => (None, None),
+        };
 
         Ok(UploadMetadata {
             version: Some(5),
             tokenless: config.token.is_none(),
             repository_provider: self.get_repository_provider(),
             ci_provider_metadata,
-            profile_md5: archive_hash.into(),
+            profile_md5: archive.hash.clone(),
+            profile_encoding: archive.encoding,
+            profile_sha256: Some(archive.sha256.clone()),
+            run_group: config.run_group.clone(),
             commit_hash,
             runner: Runner {
                 name: "codspeed-runner".into(),
                 version: crate::VERSION.into(),
                 instruments: config.instruments.get_active_instrument_names(),
                 executor: executor_name,
+                cpu_affinity,
+                resource_limits,
                 system_info: system_info.clone(),
             },
             platform: self.get_provider_slug().into(),
@@ -116,4 +169,32 @@ mod tests {
         // ensure that the commit hash is correct, thus it has 40 characters
         assert_eq!(commit_hash.len(), 40);
     }
+
+    #[test]
+    fn test_get_commit_hash_or_env() {
+        let not_a_repository = tempfile::tempdir().unwrap();
+        let not_a_repository = not_a_repository.path().to_str().unwrap();
+        temp_env::with_vars(
+            [
+                ("GITHUB_SHA", None),
+                ("CI_COMMIT_SHA", Some("HEAD")),
+                (
+                    "BUILDKITE_COMMIT",
+                    Some("5bd77cb0da72bef094893ed45fb793ff16ecfbe3"),
+                ),
+            ],
+            || {
+                assert_eq!(
+                    get_commit_hash_or_env(not_a_repository).unwrap(),
+                    "5bd77cb0da72bef094893ed45fb793ff16ecfbe3"
+                );
+            },
+        );
+        temp_env::with_vars_unset(COMMIT_HASH_ENV_VARS, || {
+            assert!(get_commit_hash_or_env(not_a_repository)
+                .unwrap_err()
+                .to_string()
+                .starts_with("Failed to open repository at path"));
+        });
+    }
 }
@@ -1,9 +1,32 @@
 use crate::prelude::*;
+use crate::run::helpers::{parse_cpu_list, parse_rate, parse_size, SudoMode};
 use crate::run::instruments::Instruments;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
 use url::Url;
 
+use crate::run::uploader::{
+    ProfileCompression, ProfileEncoding, Redaction, RunGroup, S3Target, Shard,
+};
 use crate::run::RunArgs;
 
+/// Resource limits enforced on the benchmark process through a transient cgroup
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceLimits {
+    pub cpus: Option<f64>,
+    pub memory_bytes: Option<u64>,
+}
+
+/// A long-running service started before the benchmarks and stopped after them
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceConfig {
+    pub command: String,
+    pub ready_check: Option<String>,
+    pub ready_timeout: Duration,
+}
+
 #[derive(Debug)]
 pub struct Config {
     pub upload_url: Url,
@@ -12,15 +35,44 @@ pub struct Config {
     pub command: String,
 
     pub instruments: Instruments,
+    pub cpu_affinity: Option<Vec<usize>>,
+    pub tune_system: bool,
+    pub keep_system_tuning: bool,
+    pub disable_aslr: bool,
+    pub resource_limits: Option<ResourceLimits>,
+    pub realtime_priority: Option<u8>,
+    pub cgroup_accounting: bool,
+    pub hang_timeout: Option<Duration>,
+    pub service: Option<ServiceConfig>,
+    pub sudo_mode: SudoMode,
+    pub pushgateway_url: Option<Url>,
+    pub ci_metadata_file: Option<PathBuf>,
+    pub profile_compression: ProfileCompression,
+    pub max_archive_size: Option<u64>,
+    pub redaction: Redaction,
+    pub output: Option<PathBuf>,
+    pub upload_target: Option<S3Target>,
+    pub s3_endpoint: Option<Url>,
+    /// Maximum upload bandwidth, in bytes per second
+    pub upload_rate_limit: Option<u64>,
+    pub run_group: Option<RunGroup>,
+    pub dry_run_upload: bool,
 
     pub skip_upload: bool,
     pub skip_setup: bool,
+    /// Whether the run uploads to the mock API of `--api-mode mock`
+    pub mock_api: bool,
 }
 
 impl Config {
     pub fn set_token(&mut self, token: Option<String>) {
         self.token = token;
     }
+
+    /// Whether the results are uploaded to the CodSpeed API at the end of the run
+    pub fn uploads_to_codspeed(&self) -> bool {
+        self.output.is_none() && self.upload_target.is_none() && !self.dry_run_upload
+    }
 }
 
 #[cfg(test)]
@@ -33,13 +85,35 @@ impl Config {
             working_directory: None,
             command: "".into(),
             instruments: Instruments::test(),
+            cpu_affinity: None,
+            tune_system: false,
+            keep_system_tuning: false,
+            disable_aslr: false,
+            resource_limits: None,
+            realtime_priority: None,
+            cgroup_accounting: false,
+            hang_timeout: None,
+            service: None,
+            sudo_mode: SudoMode::Auto,
+            pushgateway_url: None,
+            ci_metadata_file: None,
+            profile_compression: ProfileCompression::default(),
+            max_archive_size: None,
+            redaction: Redaction::default(),
+            output: None,
+            upload_target: None,
+            s3_endpoint: None,
+            upload_rate_limit: None,
+            run_group: None,
+            dry_run_upload: false,
             skip_upload: false,
             skip_setup: false,
+            mock_api: false,
         }
     }
 }
 
-const DEFAULT_UPLOAD_URL: &str = "https://api.codspeed.io/upload";
+pub const DEFAULT_UPLOAD_URL: &str = "https://api.codspeed.io/upload";
 
 impl TryFrom<RunArgs> for Config {
     type Error = Error;
@@ -48,14 +122,125 @@ impl TryFrom<RunArgs> for Config {
         let raw_upload_url = args.upload_url.unwrap_or_else(|| DEFAULT_UPLOAD_URL.into());
         let upload_url = Url::parse(&raw_upload_url)
             .map_err(|e| anyhow!("Invalid upload URL: {}, {}", raw_upload_url, e))?;
+        let cpu_affinity = args
+            .cpu_affinity
+            .as_deref()
+            .map(parse_cpu_list)
+            .transpose()?;
+        let memory_limit = args.memory_limit.as_deref().map(parse_size).transpose()?;
+        if let Some(cpus) = args
+            .cpu_limit
+            .filter(|cpus| !cpus.is_finite() || *cpus <= 0.0)
+        {
+            bail!(
+                "Invalid CPU limit: {}, expected a positive number of CPUs",
+                cpus
+            );
+        }
+        if memory_limit == Some(0) {
+            bail!(
+                "Invalid memory limit: {}, expected a positive size",
+                args.memory_limit.unwrap_or_default()
+            );
+        }
+        let resource_limits = match (args.cpu_limit, memory_limit) {
+            (None, None) => None,
+            (cpus, memory_bytes) => Some(ResourceLimits { cpus, memory_bytes }),
+        };
+        let service = match args.service {
+            Some(command) => Some(ServiceConfig {
+                command,
+                ready_check: args.service_ready_check,
+                ready_timeout: Duration::from_secs(args.service_ready_timeout),
+            }),
+            None if args.service_ready_check.is_some() => {
+                bail!("--service-ready-check can only be used with --service")
+            }
+            None => None,
+        };
+        if args.keep_system_tuning && !args.tune_system {
+            bail!("--keep-system-tuning can only be used with --tune-system");
+        }
+        let pushgateway_url = args
+            .pushgateway_url
+            .map(|url| {
+                Url::parse(&url).map_err(|e| anyhow!("Invalid Pushgateway URL: {}, {}", url, e))
+            })
+            .transpose()?;
+        if args.compression_threads.is_some() && args.profile_compression != ProfileEncoding::Zstd {
+            bail!("--compression-threads can only be used with --profile-compression zstd");
+        }
+        if args.output.is_some() && args.upload_target.is_some() {
+            bail!("--output and --upload-target cannot be used together");
+        }
+        if args.dry_run_upload && (args.output.is_some() || args.upload_target.is_some()) {
+            bail!("--dry-run-upload cannot be used with --output or --upload-target");
+        }
+        if args.s3_endpoint.is_some() && args.upload_target.is_none() {
+            bail!("--s3-endpoint can only be used with --upload-target");
+        }
+        let upload_target = args
+            .upload_target
+            .as_deref()
+            .map(str::parse::<S3Target>)
+            .transpose()?;
+        let s3_endpoint = args
+            .s3_endpoint
+            .map(|url| Url::parse(&url).map_err(|e| anyhow!("Invalid S3 endpoint: {}, {}", url, e)))
+            .transpose()?;
+        let shard = args.shard.as_deref().map(str::parse::<Shard>).transpose()?;
+        let run_group = match args.run_group {
+            Some(key) => Some(RunGroup { key, shard }),
+            None if shard.is_some() => bail!("--shard can only be used with --run-group"),
+            None => None,
+        };
+        let profile_compression = ProfileCompression {
+            encoding: args.profile_compression,
+            level: args.compression_level,
+            threads: args.compression_threads,
+        };
         Ok(Self {
             upload_url,
             token: args.token,
             working_directory: args.working_directory,
             instruments,
+            cpu_affinity,
+            tune_system: args.tune_system,
+            keep_system_tuning: args.keep_system_tuning,
+            disable_aslr: args.disable_aslr,
+            resource_limits,
+            realtime_priority: args.realtime_priority,
+            cgroup_accounting: args.cgroup_accounting,
+            hang_timeout: args.hang_timeout.map(Duration::from_secs),
+            service,
+            sudo_mode: args.sudo_mode,
+            pushgateway_url,
+            ci_metadata_file: args.ci_metadata_file,
+            profile_compression,
+            max_archive_size: args
+                .max_archive_size
+                .as_deref()
+                .map(parse_size)
+                .transpose()?,
+            redaction: Redaction {
+                path_prefixes: args.redact_path_prefix,
+                hash_symbols: args.redact_symbols,
+                env_vars: args.redact_env_var,
+            },
+            output: args.output,
+            upload_target,
+            s3_endpoint,
+            upload_rate_limit: args
+                .upload_rate_limit
+                .as_deref()
+                .map(parse_rate)
+                .transpose()?,
+            run_group,
+            dry_run_upload: args.dry_run_upload,
             command: args.command.join(" "),
             skip_upload: args.skip_upload,
             skip_setup: args.skip_setup,
+            mock_api: args.mock_api,
         })
     }
 }
@@ -74,8 +259,39 @@ mod tests {
             working_directory: None,
             instruments: vec![],
             mongo_uri_env_name: None,
+            cpu_affinity: None,
+            tune_system: false,
+            keep_system_tuning: false,
+            disable_aslr: false,
+            cpu_limit: None,
+            memory_limit: None,
+            realtime_priority: None,
+            cgroup_accounting: false,
+            hang_timeout: None,
+            service: None,
+            service_ready_check: None,
+            service_ready_timeout: 60,
+            sudo_mode: SudoMode::Auto,
+            pushgateway_url: None,
+            ci_metadata_file: None,
+            profile_compression: ProfileEncoding::Gzip,
+            compression_level: None,
+            compression_threads: None,
+            max_archive_size: None,
+            redact_path_prefix: vec![],
+            redact_symbols: false,
+            redact_env_var: vec![],
+            output: None,
+            upload_target: None,
+            s3_endpoint: None,
+            upload_rate_limit: None,
+            run_group: None,
+            shard: None,
+            dry_run_upload: false,
             skip_upload: false,
             skip_setup: false,
+            record_session: None,
+            mock_api: false,
             command: vec!["cargo".into(), "codspeed".into(), "bench".into()],
         })
         .unwrap();
@@ -83,6 +299,16 @@ mod tests {
         assert_eq!(config.token, None);
         assert_eq!(config.working_directory, None);
         assert_eq!(config.instruments, Instruments { mongodb: None });
+        assert_eq!(config.cpu_affinity, None);
+        assert!(!config.tune_system);
+        assert!(!config.disable_aslr);
+        assert_eq!(config.resource_limits, None);
+        assert_eq!(config.realtime_priority, None);
+        assert!(!config.cgroup_accounting);
+        assert_eq!(config.hang_timeout, None);
+        assert_eq!(config.service, None);
+        assert_eq!(config.pushgateway_url, None);
+        assert_eq!(config.ci_metadata_file, None);
         assert!(!config.skip_upload);
         assert!(!config.skip_setup);
         assert_eq!(config.command, "cargo codspeed bench");
@@ -96,8 +322,39 @@ mod tests {
             working_directory: Some("/tmp".into()),
             instruments: vec!["mongodb".into()],
             mongo_uri_env_name: Some("MONGODB_URI".into()),
+            cpu_affinity: Some("0-1,3".into()),
+            tune_system: true,
+            keep_system_tuning: true,
+            disable_aslr: true,
+            cpu_limit: Some(2.0),
+            memory_limit: Some("4GiB".into()),
+            realtime_priority: Some(50),
+            cgroup_accounting: true,
+            hang_timeout: Some(600),
+            service: Some("./server --port 8080".into()),
+            service_ready_check: Some("curl -sf localhost:8080/health".into()),
+            service_ready_timeout: 30,
+            sudo_mode: SudoMode::Cached,
+            pushgateway_url: Some("http://pushgateway:9091".into()),
+            ci_metadata_file: Some("codspeed-ci.json".into()),
+            profile_compression: ProfileEncoding::Zstd,
+            compression_level: Some(19),
+            compression_threads: Some(4),
+            max_archive_size: Some("500M".into()),
+            redact_path_prefix: vec!["/home/runner/".into()],
+            redact_symbols: true,
+            redact_env_var: vec!["DATABASE_URL".into()],
+            output: None,
+            upload_target: Some("s3://my-bucket/profiles".into()),
+            s3_endpoint: Some("http://minio:9000".into()),
+            upload_rate_limit: Some("10MB/s".into()),
+            run_group: Some("7044765741".into()),
+            shard: Some("2/4".into()),
+            dry_run_upload: false,
             skip_upload: true,
             skip_setup: true,
+            record_session: None,
+            mock_api: false,
             command: vec!["cargo".into(), "codspeed".into(), "bench".into()],
         })
         .unwrap();
@@ -116,8 +373,181 @@ mod tests {
                 })
             }
         );
+        assert_eq!(config.cpu_affinity, Some(vec![0, 1, 3]));
+        assert!(config.tune_system);
+        assert!(config.keep_system_tuning);
+        assert!(config.disable_aslr);
+        assert_eq!(
+            config.resource_limits,
+            Some(ResourceLimits {
+                cpus: Some(2.0),
+                memory_bytes: Some(4 * 1024 * 1024 * 1024)
+            })
+        );
+        assert_eq!(config.realtime_priority, Some(50));
+        assert!(config.cgroup_accounting);
+        assert_eq!(config.hang_timeout, Some(Duration::from_secs(600)));
+        assert_eq!(
+            config.service,
+            Some(ServiceConfig {
+                command: "./server --port 8080".into(),
+                ready_check: Some("curl -sf localhost:8080/health".into()),
+                ready_timeout: Duration::from_secs(30),
+            })
+        );
+        assert_eq!(config.sudo_mode, SudoMode::Cached);
+        assert_eq!(
+            config.pushgateway_url,
+            Some(Url::parse("http://pushgateway:9091").unwrap())
+        );
+        assert_eq!(
+            config.ci_metadata_file,
+            Some(PathBuf::from("codspeed-ci.json"))
+        );
+        assert_eq!(
+            config.profile_compression,
+            ProfileCompression {
+                encoding: ProfileEncoding::Zstd,
+                level: Some(19),
+                threads: Some(4),
+            }
+        );
+        assert_eq!(config.max_archive_size, Some(500 * 1024 * 1024));
+        assert_eq!(config.upload_rate_limit, Some(10_000_000));
+        assert_eq!(
+            config.run_group,
+            Some(RunGroup {
+                key: "7044765741".into(),
+                shard: Some(Shard { index: 2, count: 4 }),
+            })
+        );
+        assert_eq!(
+            config.redaction,
+            Redaction {
+                path_prefixes: vec!["/home/runner/".into()],
+                hash_symbols: true,
+                env_vars: vec!["DATABASE_URL".into()],
+            }
+        );
+        assert_eq!(config.output, None);
+        assert_eq!(
+            config.upload_target,
+            Some(S3Target {
+                bucket: "my-bucket".into(),
+                prefix: "profiles".into(),
+            })
+        );
+        assert_eq!(
+            config.s3_endpoint,
+            Some(Url::parse("http://minio:9000").unwrap())
+        );
         assert!(config.skip_upload);
         assert!(config.skip_setup);
         assert_eq!(config.command, "cargo codspeed bench");
     }
+
+    #[test]
+    fn test_try_from_args_invalid_resource_limits() {
+        for cpu_limit in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let result = Config::try_from(RunArgs {
+                cpu_limit: Some(cpu_limit),
+                ..RunArgs::test()
+            });
+            assert_eq!(
+                result.unwrap_err().to_string(),
+                format!(
+                    "Invalid CPU limit: {}, expected a positive number of CPUs",
+                    cpu_limit
+                )
+            );
+        }
+
+        let result = Config::try_from(RunArgs {
+            memory_limit: Some("0M".into()),
+            ..RunArgs::test()
+        });
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid memory limit: 0M, expected a positive size"
+        );
+        assert!(Config::try_from(RunArgs {
+            memory_limit: Some("-1G".into()),
+            ..RunArgs::test()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_try_from_args_ready_check_without_service() {
+        let result = Config::try_from(RunArgs {
+            service_ready_check: Some("true".into()),
+            ..RunArgs::test()
+        });
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "--service-ready-check can only be used with --service"
+        );
+    }
+
+    #[test]
+    fn test_try_from_args_keep_system_tuning_without_tune_system() {
+        let result = Config::try_from(RunArgs {
+            keep_system_tuning: true,
+            ..RunArgs::test()
+        });
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "--keep-system-tuning can only be used with --tune-system"
+        );
+    }
+
+    #[test]
+    fn test_try_from_args_compression_threads_with_gzip() {
+        let result = Config::try_from(RunArgs {
+            compression_threads: Some(4),
+            ..RunArgs::test()
+        });
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "--compression-threads can only be used with --profile-compression zstd"
+        );
+    }
+
+    #[test]
+    fn test_try_from_args_output_with_upload_target() {
+        let result = Config::try_from(RunArgs {
+            output: Some("bundle.tar".into()),
+            upload_target: Some("s3://my-bucket".into()),
+            ..RunArgs::test()
+        });
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "--output and --upload-target cannot be used together"
+        );
+    }
+
+    #[test]
+    fn test_try_from_args_dry_run_upload_with_output() {
+        let result = Config::try_from(RunArgs {
+            output: Some("bundle.tar".into()),
+            dry_run_upload: true,
+            ..RunArgs::test()
+        });
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "--dry-run-upload cannot be used with --output or --upload-target"
+        );
+    }
+
+    #[test]
+    fn test_try_from_args_shard_without_run_group() {
+        let result = Config::try_from(RunArgs {
+            shard: Some("1/2".into()),
+            ..RunArgs::test()
+        });
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "--shard can only be used with --run-group"
+        );
+    }
 }
@@ -1,5 +1,7 @@
 use std::path::{Path, PathBuf};
 
+use itertools::Itertools;
+
 // during normal execution, we want to find the repository root by looking for a .git directory
 // during tests, we want `find_repository_root` to always return `None`, so that we don't have to
 // create a git repository for each test
@@ -14,14 +16,72 @@ pub fn find_repository_root(_base_dir: &Path) -> Option<PathBuf> {
     None
 }
 
+/// Kind of git checkout found in a directory
+#[derive(Debug, PartialEq)]
+enum Checkout {
+    /// A regular repository, or a linked worktree
+    Repository,
+    /// A submodule, whose repository root is the one of its superproject
+    Submodule,
+}
+
+/// Read the `gitdir: <path>` pointer of a `.git` file, as used by worktrees and submodules
+fn read_gitdir_file(git_file: &Path) -> Option<PathBuf> {
+    let content = std::fs::read_to_string(git_file).ok()?;
+    let gitdir = content.trim().strip_prefix("gitdir:")?.trim();
+    // relative paths are relative to the directory containing the `.git` file
+    Some(git_file.parent()?.join(gitdir))
+}
+
+fn get_checkout(dir: &Path) -> Option<Checkout> {
+    let git_path = dir.join(".git");
+    if git_path.is_dir() {
+        return Some(Checkout::Repository);
+    }
+    if !git_path.is_file() {
+        return None;
+    }
+
+    let Some(gitdir) = read_gitdir_file(&git_path) else {
+        log::warn!("Ignoring invalid .git file at {}", git_path.display());
+        return None;
+    };
+    if !gitdir.exists() {
+        log::warn!(
+            "Ignoring .git file at {} pointing to missing {}",
+            git_path.display(),
+            gitdir.display()
+        );
+        return None;
+    }
+    // submodules are stored in `<superproject>/.git/modules/<name>`, worktrees in
+    // `<repository>/.git/worktrees/<name>`
+    let is_submodule = gitdir
+        .components()
+        .map(|component| component.as_os_str())
+        .tuple_windows()
+        .any(|(parent, child)| parent == ".git" && child == "modules");
+    if is_submodule {
+        Some(Checkout::Submodule)
+    } else {
+        Some(Checkout::Repository)
+    }
+}
+
 // the core logic is extracted into a separate function so that it can be tested
 fn _find_repository_root(base_dir: &Path) -> Option<PathBuf> {
     let current_dir = base_dir.canonicalize().ok()?;
 
     for ancestor in current_dir.ancestors() {
-        let git_dir = ancestor.join(".git");
-        if git_dir.exists() {
-            return Some(ancestor.to_path_buf());
+        match get_checkout(ancestor) {
+            Some(Checkout::Repository) => return Some(ancestor.to_path_buf()),
+            Some(Checkout::Submodule) => {
+                log::debug!(
+                    "{} is a submodule, looking for the superproject root",
+                    ancestor.display()
+                );
+            }
+            None => {}
         }
     }
 
@@ -63,4 +123,57 @@ mod tests {
 
         tmp_dir.close().unwrap();
     }
+
+    #[test]
+    fn test_find_repository_root_worktree() {
+        // a linked worktree has a .git file pointing to the worktrees directory of the main repository
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let worktree_git_dir = tmp_dir.path().join("main/.git/worktrees/feature");
+        std::fs::create_dir_all(&worktree_git_dir).unwrap();
+        let worktree_dir = tmp_dir.path().join("feature");
+        std::fs::create_dir_all(worktree_dir.join("nested")).unwrap();
+        std::fs::write(
+            worktree_dir.join(".git"),
+            format!("gitdir: {}\n", worktree_git_dir.display()),
+        )
+        .unwrap();
+
+        let repository_root = _find_repository_root(&worktree_dir.join("nested")).unwrap();
+        assert_eq!(repository_root, worktree_dir.canonicalize().unwrap());
+    }
+
+    #[test]
+    fn test_find_repository_root_submodule() {
+        // a submodule has a relative .git file pointing to the modules directory of its superproject
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let superproject_dir = tmp_dir.path().join("superproject");
+        std::fs::create_dir_all(superproject_dir.join(".git/modules/libs/sub")).unwrap();
+        let submodule_dir = superproject_dir.join("libs/sub");
+        std::fs::create_dir_all(submodule_dir.join("src")).unwrap();
+        std::fs::write(
+            submodule_dir.join(".git"),
+            "gitdir: ../../.git/modules/libs/sub\n",
+        )
+        .unwrap();
+
+        let repository_root = _find_repository_root(&submodule_dir.join("src")).unwrap();
+        assert_eq!(repository_root, superproject_dir.canonicalize().unwrap());
+    }
+
+    #[test]
+    fn test_find_repository_root_dangling_git_file() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let base_dir = tmp_dir.path().join("base-dir");
+        std::fs::create_dir_all(base_dir.join(".git")).unwrap();
+        let copied_worktree_dir = base_dir.join("copied-worktree");
+        std::fs::create_dir_all(&copied_worktree_dir).unwrap();
+        std::fs::write(
+            copied_worktree_dir.join(".git"),
+            "gitdir: /does/not/exist/.git/worktrees/copied-worktree",
+        )
+        .unwrap();
+
+        let repository_root = _find_repository_root(&copied_worktree_dir).unwrap();
+        assert_eq!(repository_root, base_dir.canonicalize().unwrap());
+    }
 }
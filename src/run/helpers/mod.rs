@@ -1,7 +1,13 @@
 mod find_repository_root;
 mod get_env_var;
+mod parse_cpu_list;
 mod parse_git_remote;
+mod parse_size;
+mod run_with_sudo;
 
 pub use find_repository_root::find_repository_root;
 pub use get_env_var::get_env_variable;
+pub use parse_cpu_list::parse_cpu_list;
 pub use parse_git_remote::*;
+pub use parse_size::{parse_rate, parse_size};
+pub use run_with_sudo::{get_sudo_command, init_sudo_mode, run_with_sudo, SudoMode};
@@ -0,0 +1,60 @@
+use anyhow::{anyhow, bail, Result};
+use std::collections::BTreeSet;
+
+/// Parse a CPU list in the format used by `taskset --cpu-list` and the kernel (e.g. `0-3,6`)
+///
+/// The returned CPU ids are sorted and deduplicated.
+pub fn parse_cpu_list(cpu_list: &str) -> Result<Vec<usize>> {
+    let parse_cpu = |cpu: &str| {
+        cpu.trim()
+            .parse::<usize>()
+            .map_err(|_| anyhow!("Invalid CPU id in CPU list: {}", cpu))
+    };
+
+    let mut cpus = BTreeSet::new();
+    for range in cpu_list.split(',') {
+        match range.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (parse_cpu(start)?, parse_cpu(end)?);
+                if start > end {
+                    bail!("Invalid CPU range in CPU list: {}", range);
+                }
+                cpus.extend(start..=end);
+            }
+            None => {
+                cpus.insert(parse_cpu(range)?);
+            }
+        }
+    }
+
+    Ok(cpus.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cpu_list() {
+        assert_eq!(parse_cpu_list("0").unwrap(), vec![0]);
+        assert_eq!(parse_cpu_list("0-3").unwrap(), vec![0, 1, 2, 3]);
+        assert_eq!(parse_cpu_list("6,0-2").unwrap(), vec![0, 1, 2, 6]);
+        assert_eq!(parse_cpu_list("1,1-2, 3").unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_parse_cpu_list_invalid() {
+        assert_eq!(
+            parse_cpu_list("").unwrap_err().to_string(),
+            "Invalid CPU id in CPU list: "
+        );
+        assert_eq!(
+            parse_cpu_list("a-2").unwrap_err().to_string(),
+            "Invalid CPU id in CPU list: a"
+        );
+        assert_eq!(
+            parse_cpu_list("3-1").unwrap_err().to_string(),
+            "Invalid CPU range in CPU list: 3-1"
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use anyhow::{anyhow, bail, Result};
+
+/// Parse a human readable size (e.g. `512M`, `4GiB`, `10MB`) into a number of bytes
+///
+/// Single letter (`K`, `M`, `G`, `T`) and binary (`KiB`, `MiB`, ...) units are powers of 1024,
+/// while decimal units (`kB`, `MB`, ...) are powers of 1000. The unit is case-insensitive.
+pub fn parse_size(size: &str) -> Result<u64> {
+    let size = size.trim();
+    let unit_start = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (value, unit) = size.split_at(unit_start);
+    let unit = unit.trim();
+    let value = value
+        .parse::<f64>()
+        .map_err(|_| anyhow!("Invalid size: {}", size))?;
+
+    let multiplier: u64 = match unit.to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kib" => 1 << 10,
+        "m" | "mib" => 1 << 20,
+        "g" | "gib" => 1 << 30,
+        "t" | "tib" => 1 << 40,
+        "kb" => 1_000,
+        "mb" => 1_000_000,
+        "gb" => 1_000_000_000,
+        "tb" => 1_000_000_000_000,
+        _ => bail!("Invalid size unit: {}", unit),
+    };
+
+    Ok((value * multiplier as f64).round() as u64)
+}
+
+/// Parse a human readable rate (e.g. `10MB/s`, `512K`) into a number of bytes per second
+pub fn parse_rate(rate: &str) -> Result<u64> {
+    let bytes_per_second = parse_size(rate.trim().trim_end_matches("/s"))?;
+    if bytes_per_second == 0 {
+        bail!("Invalid rate: {}, it must be positive", rate);
+    }
+    Ok(bytes_per_second)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1024").unwrap(), 1024);
+        assert_eq!(parse_size("12B").unwrap(), 12);
+        assert_eq!(parse_size("512M").unwrap(), 512 * 1024 * 1024);
+        assert_eq!(parse_size("4GiB").unwrap(), 4 * 1024 * 1024 * 1024);
+        assert_eq!(parse_size("1.5 KiB").unwrap(), 1536);
+        assert_eq!(parse_size("10MB").unwrap(), 10_000_000);
+        assert_eq!(parse_size("2gb").unwrap(), 2_000_000_000);
+    }
+
+    #[test]
+    fn test_parse_size_invalid() {
+        assert_eq!(
+            parse_size("GiB").unwrap_err().to_string(),
+            "Invalid size: GiB"
+        );
+        assert_eq!(
+            parse_size("4 bananas").unwrap_err().to_string(),
+            "Invalid size unit: bananas"
+        );
+    }
+
+    #[test]
+    fn test_parse_rate() {
+        assert_eq!(parse_rate("10MB/s").unwrap(), 10_000_000);
+        assert_eq!(parse_rate("512K").unwrap(), 512 * 1024);
+        assert_eq!(
+            parse_rate("0MB/s").unwrap_err().to_string(),
+            "Invalid rate: 0MB/s, it must be positive"
+        );
+    }
+}
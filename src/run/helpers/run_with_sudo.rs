@@ -0,0 +1,170 @@
+use crate::prelude::*;
+use crate::run::check_system::SystemInfo;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::io::IsTerminal;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
+
+/// How the commands requiring root privileges are run
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SudoMode {
+    /// Use sudo when available, or a passwordless doas. Fails instead of prompting for a
+    /// password in a non-interactive session.
+    #[default]
+    Auto,
+    /// Never use sudo, the privileged commands are run as the current user
+    Never,
+    /// Prompt for the sudo password once at the start of the run, then never prompt again
+    Cached,
+}
+
+/// The sudo credentials are cached for 5 minutes by default, they are refreshed well before
+const SUDO_CREDENTIALS_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+static SUDO_MODE: OnceLock<SudoMode> = OnceLock::new();
+/// Whether the runner is run by root, set along with the sudo mode
+static RUN_AS_ROOT: OnceLock<bool> = OnceLock::new();
+
+/// Whether the command exists and succeeds, `None` if it could not be executed
+fn command_succeeds(command_args: &[&str]) -> Option<bool> {
+    Command::new(command_args[0])
+        .args(&command_args[1..])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .ok()
+        .map(|status| status.success())
+}
+
+/// Refresh the cached sudo credentials in the background for the rest of the run, so that they do
+/// not expire before the privileged commands of its end, e.g. the restore of the system tuning
+fn keep_sudo_credentials_alive() {
+    thread::spawn(|| loop {
+        thread::sleep(SUDO_CREDENTIALS_REFRESH_INTERVAL);
+        if command_succeeds(&["sudo", "-n", "-v"]) != Some(true) {
+            warn!("Failed to refresh the sudo credentials, the next privileged commands may fail");
+            return;
+        }
+    });
+}
+
+/// Set the sudo mode of the run. With the `cached` mode, the sudo credentials are validated
+/// right away, so that the password is only prompted once, and kept alive until the end of the run.
+pub fn init_sudo_mode(sudo_mode: SudoMode, system_info: &SystemInfo) -> Result<()> {
+    if sudo_mode == SudoMode::Cached && system_info.user != "root" {
+        info!("Validating the sudo credentials");
+        let status = Command::new("sudo")
+            .arg("-v")
+            .status()
+            .context("Failed to execute sudo")?;
+        if !status.success() {
+            bail!("Failed to validate the sudo credentials");
+        }
+        keep_sudo_credentials_alive();
+    }
+    let sudo_mode_set = SUDO_MODE.set(sudo_mode);
+    let run_as_root_set = RUN_AS_ROOT.set(system_info.user == "root");
+    if sudo_mode_set.is_err() || run_as_root_set.is_err() {
+        debug!("The sudo mode was already set");
+    }
+    Ok(())
+}
+
+fn resolve_sudo_command(sudo_mode: SudoMode) -> Result<Vec<&'static str>> {
+    match sudo_mode {
+        SudoMode::Never => Ok(vec![]),
+        // The credentials were validated at the start of the run, and are kept alive since
+        SudoMode::Cached => Ok(vec!["sudo", "-n"]),
+        SudoMode::Auto => {
+            let passwordless_sudo = command_succeeds(&["sudo", "-n", "true"]);
+            if passwordless_sudo == Some(true) {
+                return Ok(vec!["sudo"]);
+            }
+            let sudo_available = passwordless_sudo.is_some();
+            if sudo_available && std::io::stdin().is_terminal() {
+                return Ok(vec!["sudo"]);
+            }
+            if command_succeeds(&["doas", "-n", "true"]) == Some(true) {
+                return Ok(vec!["doas", "-n"]);
+            }
+            if sudo_available {
+                bail!(
+                    "sudo requires a password, which cannot be prompted in a non-interactive session. \
+                    Configure a passwordless sudo, or use `--sudo-mode cached` from a terminal"
+                );
+            }
+            debug!("Neither sudo nor doas are available, running the privileged commands as the current user");
+            Ok(vec![])
+        }
+    }
+}
+
+fn get_sudo_prefix(run_as_root: bool, sudo_mode: SudoMode) -> Result<Vec<&'static str>> {
+    if run_as_root {
+        return Ok(vec![]);
+    }
+    resolve_sudo_command(sudo_mode)
+}
+
+/// Get the command prefix running a command with root privileges, empty when already root
+pub fn get_sudo_command(system_info: &SystemInfo) -> Result<Vec<&'static str>> {
+    get_sudo_prefix(
+        system_info.user == "root",
+        SUDO_MODE.get().copied().unwrap_or_default(),
+    )
+}
+
+/// Run a command with sudo if available and not already root, following the sudo mode of the run
+pub fn run_with_sudo(command_args: &[&str]) -> Result<()> {
+    let mut command_args: Vec<&str> = command_args.into();
+    let sudo_command = get_sudo_prefix(
+        RUN_AS_ROOT.get().copied().unwrap_or_default(),
+        SUDO_MODE.get().copied().unwrap_or_default(),
+    )?;
+    command_args.splice(0..0, sudo_command);
+
+    debug!("Running command: {}", command_args.join(" "));
+    let output = Command::new(command_args[0])
+        .args(&command_args[1..])
+        .stdout(Stdio::piped())
+        .output()
+        .map_err(|_| anyhow!("Failed to execute command: {}", command_args.join(" ")))?;
+
+    if !output.status.success() {
+        info!("stdout: {}", String::from_utf8_lossy(&output.stdout));
+        error!("stderr: {}", String::from_utf8_lossy(&output.stderr));
+        bail!("Failed to execute command: {}", command_args.join(" "));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_sudo_prefix_as_root() {
+        for sudo_mode in [SudoMode::Auto, SudoMode::Never, SudoMode::Cached] {
+            assert!(get_sudo_prefix(true, sudo_mode).unwrap().is_empty());
+        }
+        assert_eq!(
+            get_sudo_prefix(false, SudoMode::Cached).unwrap(),
+            vec!["sudo", "-n"]
+        );
+    }
+
+    #[test]
+    fn test_get_sudo_command_as_root() {
+        let system_info = SystemInfo {
+            user: "root".to_string(),
+            ..SystemInfo::test()
+        };
+        assert!(get_sudo_command(&system_info).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,67 @@
+use std::fmt;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+
+use crate::prelude::*;
+
+/// Exit code of the runner when it is interrupted, following the shell convention for SIGINT
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+type ForcedExitHook = Box<dyn Fn() + Send>;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static FORCED_EXIT_HOOKS: Mutex<Vec<ForcedExitHook>> = Mutex::new(Vec::new());
+
+/// Error returned when the run is interrupted by a SIGINT or a SIGTERM
+#[derive(Debug)]
+pub struct Interrupted;
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The run was interrupted")
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+/// Register a hook run when a second signal forces the runner to exit, to undo the changes to the
+/// system that would otherwise be restored by the normal cleanup
+pub fn on_forced_exit(hook: impl Fn() + Send + 'static) {
+    FORCED_EXIT_HOOKS.lock().unwrap().push(Box::new(hook));
+}
+
+/// Record SIGINT and SIGTERM instead of exiting right away, so that the run can stop the
+/// benchmark processes and clean up. A second signal runs the forced exit hooks and exits.
+pub fn register_signal_handlers() -> Result<()> {
+    let mut signals =
+        Signals::new([SIGINT, SIGTERM]).context("Failed to register the signal handlers")?;
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            if INTERRUPTED.swap(true, Ordering::Relaxed) {
+                warn!("Interrupted again, exiting immediately");
+                for hook in FORCED_EXIT_HOOKS.lock().unwrap().iter() {
+                    hook();
+                }
+                process::exit(INTERRUPTED_EXIT_CODE);
+            }
+        }
+    });
+    Ok(())
+}
+
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+/// Fail with an [`Interrupted`] error if a signal was received
+pub fn check_interrupted() -> Result<()> {
+    if is_interrupted() {
+        return Err(Interrupted.into());
+    }
+    Ok(())
+}
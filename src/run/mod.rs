@@ -4,21 +4,42 @@ use crate::prelude::*;
 use crate::run::{config::Config, logger::Logger};
 use crate::VERSION;
 use check_system::SystemInfo;
-use clap::Args;
+use ci_provider::CIProvider;
+use clap::{Args, FromArgMatches};
+use helpers::SudoMode;
 use instruments::mongo_tracer::MongoTracer;
-use runner::get_run_data;
+use interrupt::{check_interrupted, Interrupted};
+use run_metrics::RunMetrics;
+use run_tracer::RunTracer;
+use runner::{get_run_data, ExecutorName, RunData};
+use serde::{Deserialize, Serialize};
+use service::Service;
+use std::path::PathBuf;
+use system_tuning::SystemTuning;
+use uploader::{ProfileEncoding, RunGroup};
 
+mod bundle;
 mod check_system;
 pub mod ci_provider;
 mod helpers;
 mod instruments;
+mod interrupt;
 mod poll_results;
+mod run_metrics;
+mod run_tracer;
 mod runner;
+mod service;
+mod session;
+mod system_tuning;
 mod uploader;
 
 pub mod config;
 pub mod logger;
 
+pub use bundle::{upload, UploadArgs};
+pub use interrupt::INTERRUPTED_EXIT_CODE;
+pub use session::{replay, ReplayArgs};
+
 fn show_banner() {
     let banner = format!(
         r#"
@@ -35,7 +56,8 @@ fn show_banner() {
     debug!("codspeed v{}", VERSION);
 }
 
-#[derive(Args, Debug)]
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct RunArgs {
     /// The upload URL to use for uploading the results, useful for on-premises installations
     #[arg(long)]
@@ -43,6 +65,7 @@ pub struct RunArgs {
 
     /// The token to use for uploading the results,
     #[arg(long, env = "CODSPEED_TOKEN")]
+    #[serde(skip)]
     pub token: Option<String>,
 
     /// The directory where the command will be executed.
@@ -60,6 +83,167 @@ pub struct RunArgs {
     #[arg(long)]
     pub mongo_uri_env_name: Option<String>,
 
+    /// Pin the benchmark process to the given CPU cores, e.g. `0-3,6`.
+    ///
+    /// Only used by the walltime executor.
+    #[arg(long)]
+    pub cpu_affinity: Option<String>,
+
+    /// Tune the system for the duration of the run: use the `performance` CPU governor,
+    /// disable turbo boost and SMT. Requires root privileges or sudo.
+    #[arg(long, default_value = "false")]
+    pub tune_system: bool,
+
+    /// Keep the system tuning after the run instead of restoring the original settings, e.g. on
+    /// dedicated benchmark machines.
+    ///
+    /// Only used if `--tune-system` is enabled.
+    #[arg(long, default_value = "false")]
+    pub keep_system_tuning: bool,
+
+    /// Disable address space layout randomization for the benchmark process.
+    ///
+    /// Only used by the walltime executor, ASLR is always disabled with instrumentation.
+    #[arg(long, default_value = "false")]
+    pub disable_aslr: bool,
+
+    /// Maximum number of CPUs the benchmark process can use, e.g. `2` or `1.5`.
+    ///
+    /// Only used by the walltime executor, enforced with a transient cgroup created by `systemd-run`.
+    #[arg(long)]
+    pub cpu_limit: Option<f64>,
+
+    /// Maximum memory the benchmark process can use, e.g. `512M` or `4GiB`.
+    ///
+    /// Only used by the walltime executor, enforced with a transient cgroup created by `systemd-run`.
+    #[arg(long)]
+    pub memory_limit: Option<String>,
+
+    /// Run the benchmark process under the `SCHED_FIFO` realtime policy with the given priority
+    /// (1-99), to avoid being preempted by other tasks. Requires root privileges or sudo.
+    ///
+    /// Only used by the walltime executor, and only allowed when the kernel realtime throttling
+    /// is enabled. Best combined with `--cpu-affinity` to keep some CPUs for the rest of the system.
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=99))]
+    pub realtime_priority: Option<u8>,
+
+    /// Record the CPU, memory and IO usage of the benchmark process in the profile folder, using
+    /// the statistics of a transient cgroup created by `systemd-run`.
+    ///
+    /// Only used by the walltime executor.
+    #[arg(long, default_value = "false")]
+    pub cgroup_accounting: bool,
+
+    /// Kill the benchmark process if it does not use any CPU time for the given number of
+    /// seconds, logging the stacks of its processes when `eu-stack` is available
+    #[arg(long)]
+    pub hang_timeout: Option<u64>,
+
+    /// Command starting a long-running service (e.g. a server) before the benchmarks.
+    ///
+    /// The bench command is then measured as the load driver, and the service is stopped once it exits.
+    #[arg(long)]
+    pub service: Option<String>,
+
+    /// Command checking that the service is ready, retried until it succeeds
+    ///
+    /// Only used if a `--service` is provided.
+    #[arg(long)]
+    pub service_ready_check: Option<String>,
+
+    /// Maximum number of seconds to wait for the service to be ready
+    #[arg(long, default_value = "60")]
+    pub service_ready_timeout: u64,
+
+    /// How the commands requiring root privileges are run: `auto` uses sudo, or a passwordless
+    /// doas, and fails instead of prompting for a password in a non-interactive session, `cached`
+    /// prompts for the sudo password once at the start of the run and `never` runs them as the
+    /// current user.
+    ///
+    /// With doas, the `keepenv` option is required to run the benchmarks in a transient cgroup.
+    #[arg(long, value_enum, env = "CODSPEED_SUDO_MODE", default_value_t = SudoMode::Auto)]
+    pub sudo_mode: SudoMode,
+
+    /// URL of a Prometheus Pushgateway to push the metrics of the run to, e.g. its duration
+    #[arg(long)]
+    pub pushgateway_url: Option<String>,
+
+    /// Path to a JSON file providing the CI metadata (commit, refs, event, sender and run URL),
+    /// replacing the detection of the CI provider. Useful for CI systems not supported natively.
+    #[arg(long, env = "CODSPEED_CI_METADATA_FILE")]
+    pub ci_metadata_file: Option<PathBuf>,
+
+    /// Compression of the uploaded profile archive. zstd is faster on large profiles, e.g. with
+    /// perf data.
+    #[arg(long, value_enum, default_value_t = ProfileEncoding::Gzip)]
+    pub profile_compression: ProfileEncoding,
+
+    /// Compression level of the profile archive, defaults to the default level of the format
+    #[arg(long)]
+    pub compression_level: Option<i32>,
+
+    /// Number of threads compressing the profile archive, only supported by zstd. Defaults to the
+    /// number of available CPUs.
+    #[arg(long)]
+    pub compression_threads: Option<u32>,
+
+    /// Maximum size of the profile archive, e.g. `500M`. When exceeded, the debug info and then
+    /// the raw perf data are dropped from the archive, the results are always kept.
+    #[arg(long)]
+    pub max_archive_size: Option<String>,
+
+    /// Strip a path prefix, e.g. the home directory, from the perf maps, the callgrind profiles
+    /// and the logs before archiving them. Can be repeated.
+    #[arg(long)]
+    pub redact_path_prefix: Vec<String>,
+
+    /// Replace the symbols of the perf maps and the callgrind profiles with their hash and leave
+    /// out the debug info files when archiving them
+    #[arg(long, default_value = "false")]
+    pub redact_symbols: bool,
+
+    /// Replace the value of an environment variable in the logs with its name before archiving
+    /// them. Can be repeated.
+    #[arg(long)]
+    pub redact_env_var: Vec<String>,
+
+    /// Write the results and their upload metadata to a bundle instead of uploading them, to be
+    /// uploaded later with `codspeed upload`, e.g. from a machine with network access
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+
+    /// Upload the results and their upload metadata to an S3-compatible storage instead of
+    /// CodSpeed, e.g. `s3://bucket/prefix`, with the credentials of the standard AWS environment
+    /// variables. Useful for on-premises installations.
+    #[arg(long)]
+    pub upload_target: Option<String>,
+
+    /// Endpoint of the S3-compatible storage, defaults to `AWS_ENDPOINT_URL` or to AWS S3
+    ///
+    /// Only used if an `--upload-target` is provided.
+    #[arg(long)]
+    pub s3_endpoint: Option<String>,
+
+    /// Maximum upload bandwidth, e.g. `10MB/s`, to avoid saturating the uplink of shared
+    /// self-hosted runners
+    #[arg(long, env = "CODSPEED_UPLOAD_RATE_LIMIT")]
+    pub upload_rate_limit: Option<String>,
+
+    /// Key of a group of uploads merged into a single run, e.g. the CI run id for a benchmark
+    /// suite split across several jobs
+    #[arg(long, env = "CODSPEED_RUN_GROUP")]
+    pub run_group: Option<String>,
+
+    /// Shard of the run group uploaded by this job, as `index/count` with a 1-based index, e.g.
+    /// `2/4`. Requires `--run-group`.
+    #[arg(long, env = "CODSPEED_SHARD")]
+    pub shard: Option<String>,
+
+    /// Build the upload metadata and the profile archive, print a summary of them and exit without
+    /// contacting the CodSpeed API. Useful to debug the detection of the CI provider.
+    #[arg(long, default_value = "false")]
+    pub dry_run_upload: bool,
+
     /// Only for debugging purposes, skips the upload of the results
     #[arg(
         long,
@@ -73,96 +257,319 @@ pub struct RunArgs {
     #[arg(long, default_value = "false", hide = true)]
     pub skip_setup: bool,
 
+    /// Only for debugging purposes, records the provider environment, the arguments and the
+    /// profile folder of the run in the given directory, to be replayed with `codspeed replay`
+    #[arg(long, hide = true)]
+    pub record_session: Option<PathBuf>,
+
+    /// Set by `--api-mode mock`, the mock API does not check authentication
+    #[arg(skip)]
+    #[serde(skip)]
+    pub mock_api: bool,
+
     /// The bench command to run
     pub command: Vec<String>,
 }
 
+/// The default values of the command line, used for the arguments missing from a session recorded
+/// with an older version of the runner. The environment variables backing some arguments are
+/// ignored, only the defaults of the definitions are used.
+impl Default for RunArgs {
+    fn default() -> Self {
+        let command =
+            Self::augment_args(clap::Command::new("codspeed")).mut_args(|arg| arg.env(None));
+        Self::from_arg_matches(&command.get_matches_from(["codspeed"]))
+            .expect("The run arguments should have valid defaults")
+    }
+}
+
 #[cfg(test)]
 impl RunArgs {
     /// Constructs a new `RunArgs` with default values for testing purposes
     pub fn test() -> Self {
-        Self {
-            upload_url: None,
-            token: None,
-            working_directory: None,
-            instruments: vec![],
-            mongo_uri_env_name: None,
-            skip_upload: false,
-            skip_setup: false,
-            command: vec![],
-        }
+        Self::default()
+    }
+}
+
+/// Whether the error is caused by an interruption of the run
+pub fn is_interrupted_error(error: &Error) -> bool {
+    error.downcast_ref::<Interrupted>().is_some()
+}
+
+/// Use the token of the CodSpeed configuration file when running locally
+#[allow(clippy::borrowed_box)]
+fn set_local_token(provider: &Box<dyn CIProvider>, config: &mut Config) -> Result<()> {
+    if provider.get_provider_slug() != "local" {
+        return Ok(());
+    }
+
+    // The mock API does not check authentication
+    if config.mock_api {
+        return Ok(());
+    }
+
+    let codspeed_config = CodSpeedConfig::load()?;
+    if let Some(token) = codspeed_config.auth.token {
+        debug!("Using the token from the CodSpeed configuration file");
+        config.set_token(Some(token));
+    } else if config.uploads_to_codspeed() {
+        bail!("You have to authenticate the CLI first. Run `codspeed auth login`.");
     }
+    Ok(())
 }
 
 pub async fn run(args: RunArgs, api_client: &CodSpeedAPIClient) -> Result<()> {
+    interrupt::register_signal_handlers()?;
+    let mut run_tracer = RunTracer::new();
+    let mut run_metrics = RunMetrics::default();
+    let result = run_with_telemetry(args, api_client, &mut run_tracer, &mut run_metrics).await;
+    run_tracer.export(result.is_ok()).await;
+    run_metrics.push(&run_tracer, result.is_ok()).await;
+    result
+}
+
+async fn run_with_telemetry(
+    args: RunArgs,
+    api_client: &CodSpeedAPIClient,
+    run_tracer: &mut RunTracer,
+    run_metrics: &mut RunMetrics,
+) -> Result<()> {
+    let session_recording = args
+        .record_session
+        .clone()
+        .map(|session_dir| (session_dir, args.clone()));
     let mut config = Config::try_from(args)?;
     let provider = ci_provider::get_provider(&config)?;
-    let codspeed_config = CodSpeedConfig::load()?;
     let logger = Logger::new(&provider)?;
 
     if provider.get_provider_slug() != "local" {
         show_banner();
     }
     debug!("config: {:#?}", config);
+    run_metrics.pushgateway_url = config.pushgateway_url.clone();
 
-    if provider.get_provider_slug() == "local" {
-        if codspeed_config.auth.token.is_none() {
-            bail!("You have to authenticate the CLI first. Run `codspeed auth login`.");
-        }
-        debug!("Using the token from the CodSpeed configuration file");
-        config.set_token(codspeed_config.auth.token.clone());
-    }
-
-    let system_info = SystemInfo::new()?;
-    check_system::check_system(&system_info)?;
+    set_local_token(&provider, &mut config)?;
 
     let executor = runner::get_executor()?;
+    run_tracer.set_attribute("codspeed.provider", provider.get_provider_slug());
+    run_tracer.set_attribute("codspeed.executor", &executor.name().to_string());
+    run_tracer.set_attribute("codspeed.runner.version", VERSION);
+    run_metrics.executor = Some(executor.name().to_string());
 
-    let run_data = get_run_data()?;
-
-    if !config.skip_setup {
-        start_group!("Preparing the environment");
-        executor.setup(&config, &system_info, &run_data).await?;
-        end_group!();
-    }
+    let mut system_info = SystemInfo::new()?;
+    run_metrics.host = Some(system_info.host.clone());
+    check_system::check_system(&system_info, &executor.name(), &config)?;
+    helpers::init_sudo_mode(config.sudo_mode, &system_info)?;
 
-    start_opened_group!("Running the benchmarks");
-
-    // TODO: refactor and move directly in the Instruments struct as a `start` method
-    let mongo_tracer = if let Some(mongodb_config) = &config.instruments.mongodb {
-        let mut mongo_tracer = MongoTracer::try_from(&run_data.profile_folder, mongodb_config)?;
-        mongo_tracer.start().await?;
-        Some(mongo_tracer)
+    let system_tuning = if config.tune_system {
+        let mut system_tuning = SystemTuning::apply()?;
+        if config.keep_system_tuning {
+            system_tuning.keep();
+        }
+        system_info.cpu_tuning = Some(system_tuning.cpu_tuning.clone());
+        Some(system_tuning)
     } else {
         None
     };
 
-    executor
-        .run(&config, &system_info, &run_data, &mongo_tracer)
-        .await?;
+    let run_data = get_run_data()?;
+
+    let benchmarks_result: Result<()> = async {
+        if !config.skip_setup {
+            start_group!("Preparing the environment");
+            let setup_span = run_tracer.start_span("setup");
+            executor.setup(&config, &system_info, &run_data).await?;
+            run_tracer.end_span(setup_span);
+            end_group!();
+        }
+        check_interrupted()?;
+
+        start_opened_group!("Running the benchmarks");
+
+        // TODO: refactor and move directly in the Instruments struct as a `start` method
+        let mongo_tracer = if let Some(mongodb_config) = &config.instruments.mongodb {
+            let mut mongo_tracer = MongoTracer::try_from(&run_data.profile_folder, mongodb_config)?;
+            mongo_tracer.start().await?;
+            Some(mongo_tracer)
+        } else {
+            None
+        };
+
+        let service = config
+            .service
+            .as_ref()
+            .map(|service_config| Service::start(service_config, &config.working_directory))
+            .transpose()?;
+
+        let benchmarks_span = run_tracer.start_span("benchmarks");
+        let run_result = executor
+            .run(&config, &system_info, &run_data, &mongo_tracer)
+            .await;
+        if let Err(e) = run_result {
+            if is_interrupted_error(&e) {
+                // Still tear down to salvage the artifacts of the benchmarks that already ran, the
+                // service and the system tuning are restored when dropped
+                warn!("Running the teardown before exiting");
+                executor.teardown(&config, &system_info, &run_data).await?;
+            }
+            return Err(e);
+        }
+        run_tracer.end_span(benchmarks_span);
+
+        if let Some(mut service) = service {
+            service.stop()?;
+        }
+
+        // TODO: refactor and move directly in the Instruments struct as a `stop` method
+        if let Some(mut mongo_tracer) = mongo_tracer {
+            mongo_tracer.stop().await?;
+        }
+
+        let teardown_span = run_tracer.start_span("teardown");
+        executor.teardown(&config, &system_info, &run_data).await?;
+        run_tracer.end_span(teardown_span);
+
+        // Dropping the system tuning restores the original system settings
+        drop(system_tuning);
 
-    // TODO: refactor and move directly in the Instruments struct as a `stop` method
-    if let Some(mut mongo_tracer) = mongo_tracer {
-        mongo_tracer.stop().await?;
+        end_group!();
+        check_interrupted()?;
+        Ok(())
     }
+    .await;
+
+    // Still persist the log and record the session of a failed run, to salvage its artifacts
+    let persist_result = logger
+        .persist_log_to_profile_folder(&run_data)
+        .and_then(|()| match session_recording {
+            Some((session_dir, args)) => session::record(
+                &session_dir,
+                args,
+                provider.get_provider_slug(),
+                executor.name(),
+                &system_info,
+                &run_data,
+            ),
+            None => Ok(()),
+        });
+    benchmarks_result?;
+    persist_result?;
 
-    executor.teardown(&config, &system_info, &run_data).await?;
+    upload_results(
+        &config,
+        &system_info,
+        &provider,
+        &run_data,
+        executor.name(),
+        api_client,
+        run_tracer,
+        run_metrics,
+    )
+    .await?;
 
-    end_group!();
+    Ok(())
+}
 
-    if !config.skip_upload {
+/// Upload the results of a run, or write them to a bundle, to an S3-compatible storage, or print
+/// their summary, depending on the configuration
+#[allow(clippy::too_many_arguments, clippy::borrowed_box)]
+async fn upload_results(
+    config: &Config,
+    system_info: &SystemInfo,
+    provider: &Box<dyn CIProvider>,
+    run_data: &RunData,
+    executor_name: ExecutorName,
+    api_client: &CodSpeedAPIClient,
+    run_tracer: &mut RunTracer,
+    run_metrics: &mut RunMetrics,
+) -> Result<()> {
+    if config.dry_run_upload {
+        start_group!("Upload summary");
+        let (upload_metadata, archive) =
+            uploader::prepare_upload(config, system_info, provider, run_data, executor_name)
+                .await?;
+        info!("{}", upload_metadata.get_summary(&archive));
+        end_group!();
+    } else if let Some(bundle_path) = &config.output {
+        start_group!("Writing the bundle");
+        let (upload_metadata, archive) =
+            uploader::prepare_upload(config, system_info, provider, run_data, executor_name)
+                .await?;
+        bundle::write(bundle_path, &upload_metadata, &archive).await?;
+        end_group!();
+    } else if let Some(upload_target) = &config.upload_target {
+        start_group!("Uploading performance data");
+        let upload_span = run_tracer.start_span("upload");
+        let (upload_metadata, archive) =
+            uploader::prepare_upload(config, system_info, provider, run_data, executor_name)
+                .await?;
+        run_metrics.upload_size_bytes = Some(archive.buffer.len());
+        uploader::upload_to_s3(
+            upload_target,
+            config.s3_endpoint.as_ref(),
+            &upload_metadata,
+            archive,
+            config.upload_rate_limit,
+        )
+        .await?;
+        run_tracer.end_span(upload_span);
+        end_group!();
+    } else if !config.skip_upload {
         start_group!("Uploading performance data");
-        logger.persist_log_to_profile_folder(&run_data)?;
+        let upload_span = run_tracer.start_span("upload");
         let upload_result =
-            uploader::upload(&config, &system_info, &provider, &run_data, executor.name()).await?;
+            uploader::upload(config, system_info, provider, run_data, executor_name)
+                .await
+                .map_err(|e| {
+                    e.context(format!(
+                        "Failed to upload the results, retry without running the benchmarks again with `codspeed upload --profile-folder {}`",
+                        run_data.profile_folder.display()
+                    ))
+                })?;
+        run_tracer.end_span(upload_span);
+        run_metrics.upload_size_bytes = Some(upload_result.archive_size);
         end_group!();
 
-        if provider.get_provider_slug() == "local" {
+        let is_intermediate_shard = config
+            .run_group
+            .as_ref()
+            .is_some_and(RunGroup::is_intermediate_shard);
+        if provider.get_provider_slug() == "local" && is_intermediate_shard {
+            info!("The results of the run group are fetched by the upload of its last shard");
+        } else if provider.get_provider_slug() == "local" {
             start_group!("Fetching the results");
-            poll_results::poll_results(api_client, &provider, upload_result.run_id).await?;
+            poll_results::poll_results(
+                api_client,
+                &provider.get_ci_provider_metadata()?,
+                upload_result.run_id,
+            )
+            .await?;
             end_group!();
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_args_default() {
+        temp_env::with_vars(
+            [
+                ("CODSPEED_SKIP_UPLOAD", Some("true")),
+                ("CODSPEED_SUDO_MODE", Some("never")),
+            ],
+            || {
+                let args = RunArgs::default();
+                assert_eq!(args.service_ready_timeout, 60);
+                assert_eq!(args.sudo_mode, SudoMode::Auto);
+                assert_eq!(args.profile_compression, ProfileEncoding::Gzip);
+                assert!(!args.skip_upload);
+                assert!(args.command.is_empty());
+            },
+        );
+    }
+}
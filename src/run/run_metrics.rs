@@ -0,0 +1,179 @@
+use std::fmt::Write;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use url::Url;
+
+use crate::prelude::*;
+use crate::request_client::REQUEST_CLIENT;
+use crate::run::run_tracer::RunTracer;
+
+const PUSHGATEWAY_JOB: &str = "codspeed_runner";
+const PUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Metrics of a run, pushed to a Prometheus Pushgateway to monitor the benchmark machines
+#[derive(Default)]
+pub struct RunMetrics {
+    pub pushgateway_url: Option<Url>,
+    pub host: Option<String>,
+    pub executor: Option<String>,
+    pub upload_size_bytes: Option<usize>,
+}
+
+fn write_gauge(body: &mut String, name: &str, help: &str, samples: &[(Option<&str>, f64)]) {
+    writeln!(body, "# HELP {} {}", name, help).unwrap();
+    writeln!(body, "# TYPE {} gauge", name).unwrap();
+    for (labels, value) in samples {
+        match labels {
+            Some(labels) => writeln!(body, "{}{{{}}} {}", name, labels, value).unwrap(),
+            None => writeln!(body, "{} {}", name, value).unwrap(),
+        }
+    }
+}
+
+impl RunMetrics {
+    /// Render the metrics in the Prometheus text exposition format
+    fn to_prometheus_text(&self, run_tracer: &RunTracer, run_succeeded: bool) -> String {
+        let mut body = String::new();
+        write_gauge(
+            &mut body,
+            "codspeed_run_success",
+            "Whether the last run succeeded",
+            &[(None, if run_succeeded { 1.0 } else { 0.0 })],
+        );
+        write_gauge(
+            &mut body,
+            "codspeed_run_duration_seconds",
+            "Duration of the last run",
+            &[(None, run_tracer.run_duration().as_secs_f64())],
+        );
+        let phase_labels = run_tracer
+            .phase_durations()
+            .into_iter()
+            .map(|(phase, duration)| (format!("phase=\"{}\"", phase), duration.as_secs_f64()))
+            .collect_vec();
+        write_gauge(
+            &mut body,
+            "codspeed_run_phase_duration_seconds",
+            "Duration of the phases of the last run",
+            &phase_labels
+                .iter()
+                .map(|(labels, value)| (Some(labels.as_str()), *value))
+                .collect_vec(),
+        );
+        if let Some(upload_size_bytes) = self.upload_size_bytes {
+            write_gauge(
+                &mut body,
+                "codspeed_upload_size_bytes",
+                "Size of the profile archive uploaded by the last run",
+                &[(None, upload_size_bytes as f64)],
+            );
+        }
+        write_gauge(
+            &mut body,
+            "codspeed_run_last_timestamp_seconds",
+            "Unix timestamp of the end of the last run",
+            &[(
+                None,
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs() as f64,
+            )],
+        );
+        body
+    }
+
+    /// Get the Pushgateway URL grouping the metrics by job, instance and executor
+    fn get_push_url(&self, pushgateway_url: &Url) -> Result<Url> {
+        let mut path = format!("metrics/job/{}", PUSHGATEWAY_JOB);
+        if let Some(host) = &self.host {
+            path.push_str(&format!("/instance/{}", host));
+        }
+        if let Some(executor) = &self.executor {
+            path.push_str(&format!("/executor/{}", executor));
+        }
+        let mut base_url = pushgateway_url.clone();
+        if !base_url.path().ends_with('/') {
+            base_url.set_path(&format!("{}/", base_url.path()));
+        }
+        Ok(base_url.join(&path)?)
+    }
+
+    /// Push the metrics if a Pushgateway is configured. Failures are only logged, so that
+    /// monitoring never fails a run.
+    pub async fn push(&self, run_tracer: &RunTracer, run_succeeded: bool) {
+        let Some(pushgateway_url) = &self.pushgateway_url else {
+            return;
+        };
+        let push_url = match self.get_push_url(pushgateway_url) {
+            Ok(push_url) => push_url,
+            Err(e) => {
+                warn!("Failed to build the Pushgateway URL: {}", e);
+                return;
+            }
+        };
+        debug!("Pushing the run metrics to {}", push_url);
+
+        let response = REQUEST_CLIENT
+            .put(push_url)
+            .timeout(PUSH_TIMEOUT)
+            .header("Content-Type", "text/plain; version=0.0.4")
+            .body(self.to_prometheus_text(run_tracer, run_succeeded))
+            .send()
+            .await;
+        match response {
+            Ok(response) if response.status().is_success() => {}
+            Ok(response) => warn!(
+                "Failed to push the run metrics: {} {}",
+                response.status(),
+                response.text().await.unwrap_or_default()
+            ),
+            Err(e) => warn!("Failed to push the run metrics: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_push_url() {
+        let run_metrics = RunMetrics {
+            host: Some("bench-1".into()),
+            executor: Some("walltime".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            run_metrics
+                .get_push_url(&Url::parse("http://pushgateway:9091").unwrap())
+                .unwrap()
+                .as_str(),
+            "http://pushgateway:9091/metrics/job/codspeed_runner/instance/bench-1/executor/walltime"
+        );
+        assert_eq!(
+            RunMetrics::default()
+                .get_push_url(&Url::parse("http://monitoring/pushgateway").unwrap())
+                .unwrap()
+                .as_str(),
+            "http://monitoring/pushgateway/metrics/job/codspeed_runner"
+        );
+    }
+
+    #[test]
+    fn test_to_prometheus_text() {
+        let mut run_tracer = RunTracer::new();
+        let setup = run_tracer.start_span("setup");
+        run_tracer.end_span(setup);
+        let run_metrics = RunMetrics {
+            upload_size_bytes: Some(2048),
+            ..Default::default()
+        };
+
+        let text = run_metrics.to_prometheus_text(&run_tracer, true);
+        assert!(text.contains("# TYPE codspeed_run_success gauge\ncodspeed_run_success 1\n"));
+        assert!(text.contains("codspeed_run_phase_duration_seconds{phase=\"setup\"} "));
+        assert!(text.contains("codspeed_upload_size_bytes 2048\n"));
+        assert!(text.contains("codspeed_run_last_timestamp_seconds "));
+    }
+}
@@ -0,0 +1,306 @@
+use std::env;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rand::Rng;
+use serde_json::{json, Value};
+
+use crate::prelude::*;
+use crate::request_client::REQUEST_CLIENT;
+
+const DEFAULT_SERVICE_NAME: &str = "codspeed-runner";
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// See https://opentelemetry.io/docs/specs/otel/trace/api/#set-status
+const STATUS_CODE_OK: u8 = 1;
+const STATUS_CODE_ERROR: u8 = 2;
+
+struct Span {
+    span_id: String,
+    parent_span_id: Option<String>,
+    name: String,
+    start_time: SystemTime,
+    end_time: Option<SystemTime>,
+}
+
+/// Records the phases of a run as OpenTelemetry spans.
+///
+/// The spans are exported with OTLP/HTTP in JSON once the run is over, only if an endpoint is
+/// configured with the standard `OTEL_EXPORTER_OTLP_ENDPOINT` or
+/// `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT` environment variables.
+pub struct RunTracer {
+    trace_id: String,
+    spans: Vec<Span>,
+    attributes: Vec<(String, String)>,
+}
+
+fn random_hex_id(bytes: usize) -> String {
+    let mut rng = rand::thread_rng();
+    (0..bytes)
+        .map(|_| format!("{:02x}", rng.gen::<u8>()))
+        .join("")
+}
+
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string()
+}
+
+fn get_traces_endpoint() -> Option<String> {
+    if let Ok(endpoint) = env::var("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT") {
+        return Some(endpoint);
+    }
+    env::var("OTEL_EXPORTER_OTLP_ENDPOINT")
+        .ok()
+        .map(|endpoint| format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+}
+
+/// Parse the `key1=value1,key2=value2` format of `OTEL_EXPORTER_OTLP_HEADERS`
+fn parse_headers(headers: &str) -> Vec<(String, String)> {
+    headers
+        .split(',')
+        .filter_map(|header| header.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect()
+}
+
+fn get_headers() -> Vec<(String, String)> {
+    env::var("OTEL_EXPORTER_OTLP_TRACES_HEADERS")
+        .or_else(|_| env::var("OTEL_EXPORTER_OTLP_HEADERS"))
+        .map(|headers| parse_headers(&headers))
+        .unwrap_or_default()
+}
+
+fn string_attributes(attributes: &[(String, String)]) -> Vec<Value> {
+    attributes
+        .iter()
+        .map(|(key, value)| json!({ "key": key, "value": { "stringValue": value } }))
+        .collect()
+}
+
+impl RunTracer {
+    /// Create a tracer, starting the root span of the run
+    pub fn new() -> Self {
+        let mut run_tracer = Self {
+            trace_id: random_hex_id(16),
+            spans: vec![],
+            attributes: vec![],
+        };
+        run_tracer.start_span("codspeed run");
+        run_tracer
+    }
+
+    /// Start a span, as a child of the root span of the run, and return its index
+    pub fn start_span(&mut self, name: &str) -> usize {
+        self.spans.push(Span {
+            span_id: random_hex_id(8),
+            parent_span_id: self.spans.first().map(|root| root.span_id.clone()),
+            name: name.into(),
+            start_time: SystemTime::now(),
+            end_time: None,
+        });
+        self.spans.len() - 1
+    }
+
+    pub fn end_span(&mut self, span: usize) {
+        self.spans[span].end_time = Some(SystemTime::now());
+    }
+
+    /// Duration of the run so far, since the start of the root span
+    pub fn run_duration(&self) -> Duration {
+        self.spans[0].start_time.elapsed().unwrap_or_default()
+    }
+
+    /// Durations of the phases of the run that completed
+    pub fn phase_durations(&self) -> Vec<(&str, Duration)> {
+        self.spans
+            .iter()
+            .skip(1)
+            .filter_map(|span| {
+                let duration = span.end_time?.duration_since(span.start_time).ok()?;
+                Some((span.name.as_str(), duration))
+            })
+            .collect()
+    }
+
+    /// Add an attribute to the root span of the run
+    pub fn set_attribute(&mut self, key: &str, value: &str) {
+        self.attributes.push((key.into(), value.into()));
+    }
+
+    /// Build the OTLP JSON payload. Spans that were not ended were interrupted by an error.
+    fn to_otlp_json(&self, service_name: &str, run_succeeded: bool) -> Value {
+        let now = SystemTime::now();
+        let spans = self
+            .spans
+            .iter()
+            .enumerate()
+            .map(|(index, span)| {
+                let is_root = index == 0;
+                let succeeded = if is_root {
+                    run_succeeded
+                } else {
+                    span.end_time.is_some()
+                };
+                let mut otlp_span = json!({
+                    "traceId": self.trace_id,
+                    "spanId": span.span_id,
+                    "name": span.name,
+                    // SPAN_KIND_INTERNAL
+                    "kind": 1,
+                    "startTimeUnixNano": unix_nanos(span.start_time),
+                    "endTimeUnixNano": unix_nanos(span.end_time.unwrap_or(now)),
+                    "status": {
+                        "code": if succeeded { STATUS_CODE_OK } else { STATUS_CODE_ERROR }
+                    },
+                });
+                if let Some(parent_span_id) = &span.parent_span_id {
+                    otlp_span["parentSpanId"] = json!(parent_span_id);
+                }
+                if is_root {
+                    otlp_span["attributes"] = json!(string_attributes(&self.attributes));
+                }
+                otlp_span
+            })
+            .collect_vec();
+
+        json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": string_attributes(&[
+                        ("service.name".into(), service_name.into()),
+                        ("service.version".into(), crate::VERSION.into()),
+                    ]),
+                },
+                "scopeSpans": [{
+                    "scope": { "name": DEFAULT_SERVICE_NAME, "version": crate::VERSION },
+                    "spans": spans,
+                }],
+            }]
+        })
+    }
+
+    /// Export the spans if an OTLP endpoint is configured. Failures are only logged, so that
+    /// tracing never fails a run.
+    pub async fn export(&self, run_succeeded: bool) {
+        let Some(endpoint) = get_traces_endpoint() else {
+            return;
+        };
+        let service_name =
+            env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| DEFAULT_SERVICE_NAME.into());
+        debug!("Exporting the run spans to {}", endpoint);
+
+        let mut request = REQUEST_CLIENT
+            .post(&endpoint)
+            .timeout(EXPORT_TIMEOUT)
+            .json(&self.to_otlp_json(&service_name, run_succeeded));
+        for (key, value) in get_headers() {
+            request = request.header(key, value);
+        }
+        match request.send().await {
+            Ok(response) if response.status().is_success() => {}
+            Ok(response) => warn!(
+                "Failed to export the run spans: {} {}",
+                response.status(),
+                response.text().await.unwrap_or_default()
+            ),
+            Err(e) => warn!("Failed to export the run spans: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_traces_endpoint() {
+        temp_env::with_vars(
+            [
+                (
+                    "OTEL_EXPORTER_OTLP_ENDPOINT",
+                    Some("http://collector:4318/"),
+                ),
+                ("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT", None),
+            ],
+            || {
+                assert_eq!(
+                    get_traces_endpoint(),
+                    Some("http://collector:4318/v1/traces".into())
+                );
+            },
+        );
+        temp_env::with_vars(
+            [
+                ("OTEL_EXPORTER_OTLP_ENDPOINT", Some("http://collector:4318")),
+                (
+                    "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+                    Some("http://traces:4318/custom"),
+                ),
+            ],
+            || {
+                assert_eq!(
+                    get_traces_endpoint(),
+                    Some("http://traces:4318/custom".into())
+                );
+            },
+        );
+        temp_env::with_vars_unset(
+            [
+                "OTEL_EXPORTER_OTLP_ENDPOINT",
+                "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+            ],
+            || {
+                assert_eq!(get_traces_endpoint(), None);
+            },
+        );
+    }
+
+    #[test]
+    fn test_parse_headers() {
+        assert_eq!(
+            parse_headers("api-key=secret, x-team = perf,invalid"),
+            vec![
+                ("api-key".to_string(), "secret".to_string()),
+                ("x-team".to_string(), "perf".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_otlp_json() {
+        let mut run_tracer = RunTracer::new();
+        run_tracer.set_attribute("codspeed.executor", "walltime");
+        let setup = run_tracer.start_span("setup");
+        run_tracer.end_span(setup);
+        run_tracer.start_span("benchmarks");
+
+        let otlp_json = run_tracer.to_otlp_json("my-service", false);
+        let resource_spans = &otlp_json["resourceSpans"][0];
+        assert_eq!(
+            resource_spans["resource"]["attributes"][0]["value"]["stringValue"],
+            "my-service"
+        );
+        let spans = resource_spans["scopeSpans"][0]["spans"].as_array().unwrap();
+        assert_eq!(spans.len(), 3);
+
+        let root_span = &spans[0];
+        assert_eq!(root_span["name"], "codspeed run");
+        assert_eq!(root_span["traceId"].as_str().unwrap().len(), 32);
+        assert_eq!(root_span["spanId"].as_str().unwrap().len(), 16);
+        assert_eq!(root_span["parentSpanId"], Value::Null);
+        assert_eq!(root_span["status"]["code"], STATUS_CODE_ERROR);
+        assert_eq!(root_span["attributes"][0]["key"], "codspeed.executor");
+
+        assert_eq!(spans[1]["name"], "setup");
+        assert_eq!(spans[1]["parentSpanId"], root_span["spanId"]);
+        assert_eq!(spans[1]["status"]["code"], STATUS_CODE_OK);
+        assert_eq!(spans[2]["name"], "benchmarks");
+        assert_eq!(spans[2]["status"]["code"], STATUS_CODE_ERROR);
+
+        let phase_durations = run_tracer.phase_durations();
+        assert_eq!(phase_durations.len(), 1);
+        assert_eq!(phase_durations[0].0, "setup");
+    }
+}
@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+use std::fs;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::prelude::*;
+use crate::run::helpers::run_with_sudo;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// CPU time of a process, along with its parent pid
+#[derive(Debug, PartialEq)]
+struct ProcessStat {
+    ppid: u32,
+    /// CPU time of the process and of its waited-for children, in clock ticks
+    cpu_time: u64,
+}
+
+/// Parse the content of `/proc/<pid>/stat`
+fn parse_process_stat(stat: &str) -> Option<ProcessStat> {
+    // The command name can contain spaces and parentheses, the fields start after the last ')'
+    let fields = stat[stat.rfind(')')? + 1..]
+        .split_whitespace()
+        .collect_vec();
+    let ppid = fields.get(1)?.parse().ok()?;
+    // utime, stime, cutime and cstime
+    let cpu_time = fields
+        .get(11..15)?
+        .iter()
+        .map(|field| field.parse::<u64>().ok())
+        .sum::<Option<u64>>()?;
+    Some(ProcessStat { ppid, cpu_time })
+}
+
+fn get_process_stats() -> HashMap<u32, ProcessStat> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return HashMap::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+        .filter_map(|pid| {
+            let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+            Some((pid, parse_process_stat(&stat)?))
+        })
+        .collect()
+}
+
+/// Get the pids of the process tree rooted at `root_pid`, parents first
+fn get_process_tree(root_pid: u32, process_stats: &HashMap<u32, ProcessStat>) -> Vec<u32> {
+    let mut tree = vec![root_pid];
+    let mut index = 0;
+    while index < tree.len() {
+        let parent = tree[index];
+        tree.extend(
+            process_stats
+                .iter()
+                .filter(|(_, stat)| stat.ppid == parent)
+                .map(|(pid, _)| *pid)
+                .sorted(),
+        );
+        index += 1;
+    }
+    tree
+}
+
+/// Send a signal to every process of the tree, children first.
+///
+/// The signal is sent with a plain `kill`. Only when the tree was started through sudo, e.g. by
+/// the cgroup or the realtime wrappers, its root can be owned by root and the signal is sent
+/// again with sudo if the plain `kill` fails.
+fn signal_process_tree(process_tree: &[u32], signal: &str, started_with_sudo: bool) -> Result<()> {
+    let pids = process_tree
+        .iter()
+        .rev()
+        .map(|pid| pid.to_string())
+        .collect_vec();
+    let mut command_args = vec!["kill", signal];
+    command_args.extend(pids.iter().map(|pid| pid.as_str()));
+    let error_message = format!(
+        "Failed to send SIG{} to the benchmark processes",
+        signal.trim_start_matches('-')
+    );
+
+    let signaled = Command::new(command_args[0])
+        .args(&command_args[1..])
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    if signaled {
+        return Ok(());
+    }
+    if !started_with_sudo {
+        bail!(error_message);
+    }
+    debug!("Failed to send the signal with kill, retrying with sudo");
+    run_with_sudo(&command_args).context(error_message)
+}
+
+/// Send a signal to the benchmark process rooted at `root_pid` and to all of its descendants
+pub fn signal_benchmark_processes(
+    root_pid: u32,
+    signal: &str,
+    started_with_sudo: bool,
+) -> Result<()> {
+    signal_process_tree(
+        &get_process_tree(root_pid, &get_process_stats()),
+        signal,
+        started_with_sudo,
+    )
+}
+
+/// Log the stack of every process of the tree, if `eu-stack` is available
+fn log_stack_snapshots(process_tree: &[u32]) {
+    for pid in process_tree {
+        match Command::new("eu-stack")
+            .args(["-p", &pid.to_string()])
+            .output()
+        {
+            Ok(output) => warn!(
+                "Stack of process {}:\n{}{}",
+                pid,
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            ),
+            Err(_) => {
+                warn!(
+                    "eu-stack is not available, skipping the stack snapshot of the hung processes"
+                );
+                return;
+            }
+        }
+    }
+}
+
+/// Detects benchmark processes that stopped making progress.
+///
+/// A process tree is considered hung when its total CPU time does not change between two samples
+/// for `timeout`. It can decrease, when a process exits before being waited for by its parent.
+pub struct HangWatchdog {
+    timeout: Duration,
+    started_with_sudo: bool,
+    last_cpu_time: Option<u64>,
+    last_progress: Instant,
+    last_check: Option<Instant>,
+}
+
+impl HangWatchdog {
+    pub fn new(timeout: Duration, started_with_sudo: bool) -> Self {
+        Self {
+            timeout,
+            started_with_sudo,
+            last_cpu_time: None,
+            last_progress: Instant::now(),
+            last_check: None,
+        }
+    }
+
+    /// Record a sample of the CPU time of the process tree, returning whether it is hung
+    fn record_cpu_time(&mut self, cpu_time: u64) -> bool {
+        if self.last_cpu_time.replace(cpu_time) != Some(cpu_time) {
+            self.last_progress = Instant::now();
+            return false;
+        }
+        self.last_progress.elapsed() >= self.timeout
+    }
+
+    /// Check the progress of the process tree rooted at `pid`.
+    ///
+    /// If it is hung, the stacks of its processes are logged, they are killed and an error is returned.
+    /// The process tree is only inspected once per `CHECK_INTERVAL`, so this can be called often.
+    pub fn check(&mut self, pid: u32) -> Result<()> {
+        if self
+            .last_check
+            .is_some_and(|last_check| last_check.elapsed() < CHECK_INTERVAL)
+        {
+            return Ok(());
+        }
+        self.last_check = Some(Instant::now());
+
+        let process_stats = get_process_stats();
+        let process_tree = get_process_tree(pid, &process_stats);
+        let cpu_time = process_tree
+            .iter()
+            .filter_map(|pid| process_stats.get(pid))
+            .map(|stat| stat.cpu_time)
+            .sum();
+
+        if !self.record_cpu_time(cpu_time) {
+            return Ok(());
+        }
+
+        log_stack_snapshots(&process_tree);
+        if let Err(e) = signal_process_tree(&process_tree, "-KILL", self.started_with_sudo) {
+            bail!(
+                "The benchmark process made no progress for {} seconds and could not be killed: {:#}",
+                self.timeout.as_secs(),
+                e
+            );
+        }
+        bail!(
+            "The benchmark process was killed after making no progress for {} seconds",
+            self.timeout.as_secs()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_process_stat() {
+        let stat = "1234 (my (weird) cmd) S 42 1234 1234 0 -1 4194560 1000 0 0 0 150 25 10 5 20 0 1 0 100 1000000 200 18446744073709551615";
+        assert_eq!(
+            parse_process_stat(stat),
+            Some(ProcessStat {
+                ppid: 42,
+                cpu_time: 190
+            })
+        );
+        assert_eq!(parse_process_stat("1234 (cmd) S 42"), None);
+    }
+
+    #[test]
+    fn test_record_cpu_time() {
+        let mut hang_watchdog = HangWatchdog::new(Duration::ZERO, false);
+        assert!(!hang_watchdog.record_cpu_time(100));
+        assert!(hang_watchdog.record_cpu_time(100));
+        // A child exiting before being waited for lowers the CPU time of the tree
+        assert!(!hang_watchdog.record_cpu_time(60));
+        assert!(hang_watchdog.record_cpu_time(60));
+        assert!(!hang_watchdog.record_cpu_time(80));
+    }
+
+    #[test]
+    fn test_signal_process_tree() {
+        let mut process = Command::new("sleep").arg("100").spawn().unwrap();
+        signal_process_tree(&[process.id()], "-KILL", false).unwrap();
+        assert_eq!(
+            std::os::unix::process::ExitStatusExt::signal(&process.wait().unwrap()),
+            Some(9)
+        );
+        assert!(signal_process_tree(&[process.id()], "-KILL", false).is_err());
+    }
+
+    #[test]
+    fn test_get_process_tree() {
+        let process_stats = HashMap::from([
+            (
+                1,
+                ProcessStat {
+                    ppid: 0,
+                    cpu_time: 0,
+                },
+            ),
+            (
+                10,
+                ProcessStat {
+                    ppid: 1,
+                    cpu_time: 0,
+                },
+            ),
+            (
+                11,
+                ProcessStat {
+                    ppid: 10,
+                    cpu_time: 0,
+                },
+            ),
+            (
+                13,
+                ProcessStat {
+                    ppid: 11,
+                    cpu_time: 0,
+                },
+            ),
+            (
+                12,
+                ProcessStat {
+                    ppid: 10,
+                    cpu_time: 0,
+                },
+            ),
+            (
+                20,
+                ProcessStat {
+                    ppid: 1,
+                    cpu_time: 0,
+                },
+            ),
+        ]);
+        assert_eq!(get_process_tree(10, &process_stats), vec![10, 11, 12, 13]);
+    }
+}
@@ -1,4 +1,7 @@
 pub mod env;
 pub mod get_bench_command;
+pub mod hang_watchdog;
 pub mod profile_folder;
+pub mod realtime;
+pub mod resource_limits;
 pub mod run_command_with_log_pipe;
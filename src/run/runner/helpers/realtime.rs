@@ -0,0 +1,100 @@
+use std::fs;
+
+use crate::prelude::*;
+
+const RT_RUNTIME_PATH: &str = "/proc/sys/kernel/sched_rt_runtime_us";
+const RT_PERIOD_PATH: &str = "/proc/sys/kernel/sched_rt_period_us";
+/// Share of each period below which the realtime benchmarks are throttled while they run. The
+/// default budget of the kernel is 95%.
+const MIN_RT_BUDGET: f64 = 0.9;
+
+/// Get the share of each period that realtime tasks are allowed to use, failing if the
+/// realtime throttling is disabled
+fn get_rt_budget(runtime_us: &str, period_us: &str) -> Result<f64> {
+    let runtime_us: i64 = runtime_us.trim().parse()?;
+    let period_us: i64 = period_us.trim().parse()?;
+    if period_us <= 0 {
+        bail!(
+            "Invalid realtime period ({} is {})",
+            RT_PERIOD_PATH,
+            period_us
+        );
+    }
+    if runtime_us < 0 {
+        bail!(
+            "Realtime throttling is disabled ({} is -1), a runaway realtime benchmark could lock up the system",
+            RT_RUNTIME_PATH
+        );
+    }
+    Ok(runtime_us as f64 / period_us as f64)
+}
+
+/// Check that the kernel throttles realtime tasks, so that a benchmark running under
+/// `SCHED_FIFO` cannot starve the rest of the system
+pub fn check_rt_throttling() -> Result<()> {
+    let runtime_us = fs::read_to_string(RT_RUNTIME_PATH)
+        .context(format!("Failed to read {}", RT_RUNTIME_PATH))?;
+    let period_us =
+        fs::read_to_string(RT_PERIOD_PATH).context(format!("Failed to read {}", RT_PERIOD_PATH))?;
+    let rt_budget = get_rt_budget(&runtime_us, &period_us)?;
+    if rt_budget < MIN_RT_BUDGET {
+        warn!(
+            "Realtime tasks are throttled to {}% of the CPU time, the benchmarks will be paused while throttled and their measurements skewed. Raise {} to at least {}% of {}",
+            (rt_budget * 100.0).round(),
+            RT_RUNTIME_PATH,
+            (MIN_RT_BUDGET * 100.0).round(),
+            RT_PERIOD_PATH
+        );
+    } else {
+        info!(
+            "Realtime tasks are throttled to {}% of the CPU time",
+            (rt_budget * 100.0).round()
+        );
+    }
+    Ok(())
+}
+
+/// Prepend the bench command with a switch of the shell to `SCHED_FIFO`, so that every process of
+/// the benchmark inherits the realtime policy. Only the scheduling change is run with the
+/// `sudo_command`.
+pub fn wrap_with_realtime_scheduling(
+    bench_command: &str,
+    priority: u8,
+    sudo_command: &[&str],
+) -> String {
+    let sudo = sudo_command.iter().map(|arg| format!("{} ", arg)).join("");
+    format!(
+        "{}chrt --fifo --pid {} $$ || exit 1\n{}",
+        sudo, priority, bench_command
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_rt_budget() {
+        assert_eq!(get_rt_budget("950000\n", "1000000\n").unwrap(), 0.95);
+        assert_eq!(
+            get_rt_budget("-1", "1000000").unwrap_err().to_string(),
+            "Realtime throttling is disabled (/proc/sys/kernel/sched_rt_runtime_us is -1), a runaway realtime benchmark could lock up the system"
+        );
+        assert_eq!(
+            get_rt_budget("950000", "0").unwrap_err().to_string(),
+            "Invalid realtime period (/proc/sys/kernel/sched_rt_period_us is 0)"
+        );
+    }
+
+    #[test]
+    fn test_wrap_with_realtime_scheduling() {
+        assert_eq!(
+            wrap_with_realtime_scheduling("pytest tests/", 50, &["sudo", "-n"]),
+            "sudo -n chrt --fifo --pid 50 $$ || exit 1\npytest tests/"
+        );
+        assert_eq!(
+            wrap_with_realtime_scheduling("pytest tests/", 1, &[]),
+            "chrt --fifo --pid 1 $$ || exit 1\npytest tests/"
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use std::env;
+use std::path::Path;
+
+use crate::run::{check_system::SystemInfo, config::ResourceLimits};
+
+/// Files of the cgroup v2 interface dumped when the cgroup accounting is enabled
+const CGROUP_STAT_FILES: [&str; 3] = ["cpu.stat", "memory.peak", "io.stat"];
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Get the command prefix running a command in a transient cgroup, enforcing the resource limits
+/// and enabling the CPU, memory and IO accounting if requested
+///
+/// The cgroup is created by `systemd-run`, which requires root privileges and is run with the
+/// `sudo_command`. The command itself is still executed as the current user, with the current
+/// environment.
+pub fn get_transient_cgroup_cmd_prefix(
+    resource_limits: Option<&ResourceLimits>,
+    accounting: bool,
+    system_info: &SystemInfo,
+    sudo_command: &[&str],
+) -> Vec<String> {
+    let mut cmd_prefix: Vec<String> = vec![];
+    if !sudo_command.is_empty() {
+        cmd_prefix.extend(sudo_command.iter().map(|arg| arg.to_string()));
+        if sudo_command[0] == "sudo" {
+            cmd_prefix.push("--preserve-env".into());
+        }
+        cmd_prefix.extend([
+            // sudo resets the PATH, forward it explicitly
+            "env".into(),
+            format!("PATH={}", env::var("PATH").unwrap_or_default()),
+        ]);
+    }
+    cmd_prefix.extend([
+        "systemd-run".into(),
+        "--scope".into(),
+        "--quiet".into(),
+        format!("--uid={}", system_info.user),
+    ]);
+    if accounting {
+        for property in [
+            "CPUAccounting=yes",
+            "MemoryAccounting=yes",
+            "IOAccounting=yes",
+        ] {
+            cmd_prefix.extend(["-p".into(), property.into()]);
+        }
+    }
+    if let Some(cpus) = resource_limits.and_then(|limits| limits.cpus) {
+        cmd_prefix.extend(["-p".into(), format!("CPUQuota={}%", (cpus * 100.0).round())]);
+    }
+    if let Some(memory_bytes) = resource_limits.and_then(|limits| limits.memory_bytes) {
+        cmd_prefix.extend([
+            "-p".into(),
+            format!("MemoryMax={}", memory_bytes),
+            "-p".into(),
+            "MemorySwapMax=0".into(),
+        ]);
+    }
+    cmd_prefix.push("--".into());
+    cmd_prefix
+}
+
+/// Wrap the bench command to copy the statistics of its cgroup to `output_dir` once it exits.
+///
+/// The transient cgroup is removed as soon as its last process exits, so the statistics have to
+/// be read from inside of it. Since the cgroup is created for the run, they only cover the benchmarks.
+/// The bench command runs in a subshell, so that its `exit` does not skip the copy.
+pub fn wrap_with_cgroup_stats_dump(bench_command: &str, output_dir: &Path) -> String {
+    wrap_with_cgroup_stats_dump_from(bench_command, Path::new(CGROUP_ROOT), output_dir)
+}
+
+fn wrap_with_cgroup_stats_dump_from(
+    bench_command: &str,
+    cgroup_root: &Path,
+    output_dir: &Path,
+) -> String {
+    format!(
+        r#"(
+{}
+)
+status=$?
+cgroup_path={}$(sed -n 's/^0:://p' /proc/self/cgroup)
+for stat_file in {}; do
+    cp "$cgroup_path/$stat_file" "{}/$stat_file" 2>/dev/null
+done
+exit $status"#,
+        bench_command,
+        cgroup_root.display(),
+        CGROUP_STAT_FILES.join(" "),
+        output_dir.display()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use temp_env::with_var;
+
+    use super::*;
+
+    #[test]
+    fn test_get_resource_limits_cmd_prefix() {
+        let resource_limits = ResourceLimits {
+            cpus: Some(1.5),
+            memory_bytes: Some(1024),
+        };
+        with_var("PATH", Some("/usr/bin:/bin"), || {
+            assert_eq!(
+                get_transient_cgroup_cmd_prefix(
+                    Some(&resource_limits),
+                    false,
+                    &SystemInfo::test(),
+                    &["sudo"]
+                )
+                    .join(" "),
+                "sudo --preserve-env env PATH=/usr/bin:/bin systemd-run --scope --quiet --uid=user -p CPUQuota=150% -p MemoryMax=1024 -p MemorySwapMax=0 --"
+            );
+        });
+    }
+
+    #[test]
+    fn test_get_resource_limits_cmd_prefix_root() {
+        let resource_limits = ResourceLimits {
+            cpus: Some(2.0),
+            memory_bytes: None,
+        };
+        let system_info = SystemInfo {
+            user: "root".into(),
+            ..SystemInfo::test()
+        };
+        assert_eq!(
+            get_transient_cgroup_cmd_prefix(Some(&resource_limits), false, &system_info, &[])
+                .join(" "),
+            "systemd-run --scope --quiet --uid=root -p CPUQuota=200% --"
+        );
+    }
+
+    #[test]
+    fn test_get_transient_cgroup_cmd_prefix_accounting() {
+        let system_info = SystemInfo {
+            user: "root".into(),
+            ..SystemInfo::test()
+        };
+        assert_eq!(
+            get_transient_cgroup_cmd_prefix(None, true, &system_info, &[]).join(" "),
+            "systemd-run --scope --quiet --uid=root -p CPUAccounting=yes -p MemoryAccounting=yes -p IOAccounting=yes --"
+        );
+    }
+
+    #[test]
+    fn test_wrap_with_cgroup_stats_dump() {
+        let wrapped_command = wrap_with_cgroup_stats_dump("exit 3", Path::new("/tmp/stats"));
+        assert!(wrapped_command.starts_with("(\nexit 3\n)\nstatus=$?\n"));
+
+        // Fake the cgroup of the current process, which is inherited by the shell
+        let cgroup_root = tempfile::tempdir().unwrap();
+        let cgroup_path = std::fs::read_to_string("/proc/self/cgroup")
+            .unwrap_or_default()
+            .lines()
+            .find_map(|line| line.strip_prefix("0::").map(|path| path.to_string()))
+            .unwrap_or_default();
+        let cgroup_dir = cgroup_root.path().join(cgroup_path.trim_start_matches('/'));
+        std::fs::create_dir_all(&cgroup_dir).unwrap();
+        std::fs::write(cgroup_dir.join("cpu.stat"), "usage_usec 42\n").unwrap();
+
+        let output_dir = tempfile::tempdir().unwrap();
+        let status = std::process::Command::new("sh")
+            .args([
+                "-c",
+                &wrap_with_cgroup_stats_dump_from(
+                    "echo first\nexit 3",
+                    cgroup_root.path(),
+                    output_dir.path(),
+                ),
+            ])
+            .status()
+            .unwrap();
+        assert_eq!(status.code(), Some(3));
+        assert_eq!(
+            std::fs::read_to_string(output_dir.path().join("cpu.stat")).unwrap(),
+            "usage_usec 42\n"
+        );
+    }
+}
@@ -1,12 +1,66 @@
+use super::hang_watchdog::{signal_benchmark_processes, HangWatchdog};
 use crate::local_logger::suspend_progress_bar;
 use crate::prelude::*;
+use crate::run::interrupt::{is_interrupted, Interrupted};
 use crate::run::runner::EXECUTOR_TARGET;
 use std::io::{Read, Write};
 use std::process::Command;
-use std::process::ExitStatus;
+use std::process::{Child, ExitStatus};
 use std::thread;
+use std::time::{Duration, Instant};
 
-pub fn run_command_with_log_pipe(mut cmd: Command) -> Result<ExitStatus> {
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+const INTERRUPT_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Wait for the process to exit, returning `false` if it is still running after `timeout`
+fn wait_with_timeout(process: &mut Child, timeout: Duration) -> Result<bool> {
+    let start = Instant::now();
+    while process.try_wait()?.is_none() {
+        if start.elapsed() >= timeout {
+            return Ok(false);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    Ok(true)
+}
+
+/// Stop the interrupted benchmark processes, killing them if they do not exit after a SIGTERM.
+///
+/// The failures are only logged, so that the run is still reported as interrupted and torn down.
+fn terminate_interrupted_process(process: &mut Child, started_with_sudo: bool) {
+    warn!("Interrupted, stopping the benchmark processes");
+    if let Err(e) = signal_benchmark_processes(process.id(), "-TERM", started_with_sudo) {
+        warn!("{:#}", e);
+    }
+    match wait_with_timeout(process, INTERRUPT_GRACE_PERIOD) {
+        Ok(true) => return,
+        Ok(false) => {}
+        Err(e) => {
+            warn!("Failed to wait for the benchmark processes: {:#}", e);
+            return;
+        }
+    }
+    warn!("The benchmark processes did not stop after a SIGTERM, killing them");
+    if let Err(e) = signal_benchmark_processes(process.id(), "-KILL", started_with_sudo) {
+        warn!("{:#}, they may still be running", e);
+        return;
+    }
+    if let Err(e) = process.wait() {
+        warn!("Failed to wait for the killed benchmark processes: {:#}", e);
+    }
+}
+
+/// Run the command, piping its output to the logs.
+///
+/// If a `hang_timeout` is provided, the process is killed when it makes no progress for that long.
+/// If the run is interrupted, the process is stopped and an [`Interrupted`] error is returned.
+/// `started_with_sudo` tells whether the command runs through sudo, so that the signals are sent
+/// with sudo if needed.
+pub fn run_command_with_log_pipe(
+    mut cmd: Command,
+    hang_timeout: Option<Duration>,
+    started_with_sudo: bool,
+) -> Result<ExitStatus> {
     fn log_tee(
         mut reader: impl Read,
         mut writer: impl Write,
@@ -46,5 +100,33 @@ pub fn run_command_with_log_pipe(mut cmd: Command) -> Result<ExitStatus> {
     thread::spawn(move || {
         log_tee(stderr, std::io::stderr(), Some("[stderr]")).unwrap();
     });
-    process.wait().context("failed to wait for the process")
+
+    let mut hang_watchdog =
+        hang_timeout.map(|hang_timeout| HangWatchdog::new(hang_timeout, started_with_sudo));
+    loop {
+        if let Some(status) = process
+            .try_wait()
+            .context("failed to wait for the process")?
+        {
+            // The benchmark processes also receive the SIGINT of a Ctrl-C
+            if is_interrupted() {
+                return Err(Interrupted.into());
+            }
+            return Ok(status);
+        }
+        if is_interrupted() {
+            terminate_interrupted_process(&mut process, started_with_sudo);
+            return Err(Interrupted.into());
+        }
+        if let Some(Err(e)) = hang_watchdog
+            .as_mut()
+            .map(|hang_watchdog| hang_watchdog.check(process.id()))
+        {
+            // Reap the killed process before reporting the hang, without blocking if it could not
+            // be killed
+            let _ = wait_with_timeout(&mut process, INTERRUPT_GRACE_PERIOD);
+            return Err(e);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
 }
@@ -40,6 +40,45 @@ fn get_node_objects() -> Vec<String> {
     vec![so_output]
 }
 
+fn get_ruby_objects() -> Vec<String> {
+    // libruby is only built when ruby is configured with --enable-shared
+    let output = Command::new("ruby")
+        .arg("-e")
+        .arg(r#"require "rbconfig"; print(RbConfig::CONFIG["ENABLE_SHARED"] == "yes" ? File.join(RbConfig::CONFIG["libdir"], RbConfig::CONFIG["LIBRUBY_SO"]) : RbConfig.ruby)"#)
+        .output();
+
+    if output.is_err() {
+        debug!("Failed to get ruby shared objects: {:?}", output.err());
+        return vec![];
+    }
+    let output = output.unwrap();
+    if !output.status.success() {
+        debug!("Failed to get ruby shared objects: {:?}", output.stderr);
+        return vec![];
+    }
+    let so_output = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    vec![so_output]
+}
+
+fn get_php_objects() -> Vec<String> {
+    let output = Command::new("php")
+        .arg("-r")
+        .arg("echo PHP_BINARY;")
+        .output();
+
+    if output.is_err() {
+        debug!("Failed to get php shared objects: {:?}", output.err());
+        return vec![];
+    }
+    let output = output.unwrap();
+    if !output.status.success() {
+        debug!("Failed to get php shared objects: {:?}", output.stderr);
+        return vec![];
+    }
+    let so_output = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    vec![so_output]
+}
+
 fn normalize_object_paths(objects_path_to_ignore: &mut [String]) {
     for path in objects_path_to_ignore.iter_mut() {
         let cpath = PathBuf::from(&path).canonicalize();
@@ -55,6 +94,8 @@ pub fn get_objects_path_to_ignore() -> Vec<String> {
     let mut objects_path_to_ignore = vec![];
     objects_path_to_ignore.extend(get_python_objects());
     objects_path_to_ignore.extend(get_node_objects());
+    objects_path_to_ignore.extend(get_ruby_objects());
+    objects_path_to_ignore.extend(get_php_objects());
     debug!(
         "objects_path_to_ignore before normalization: {:?}",
         objects_path_to_ignore
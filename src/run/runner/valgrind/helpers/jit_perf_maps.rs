@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+use std::env;
+use std::path::Path;
+
+/// Commands starting a JVM, either directly or through a build tool
+const JVM_COMMANDS: [&str; 7] = ["java", "gradle", "gradlew", "mvn", "mvnw", "sbt", "kotlin"];
+const DOTNET_COMMANDS: [&str; 1] = ["dotnet"];
+
+/// Make the JVM write its perf map to `/tmp/perf-<pid>.map` when exiting, so that the JIT-compiled
+/// Java frames can be symbolized. `DumpPerfMapAtExit` is available since JDK 17, older JVMs ignore it.
+const JVM_PERF_MAP_OPTIONS: &str =
+    "-XX:+UnlockDiagnosticVMOptions -XX:+IgnoreUnrecognizedVMOptions -XX:+DumpPerfMapAtExit";
+
+/// Whether one of the commands is the executable of a segment of the bench command, the segments
+/// being separated by `&&`, `||`, `;`, `|` or a newline
+fn is_command_using(bench_command: &str, commands: &[&str]) -> bool {
+    bench_command
+        .split(['&', '|', ';', '\n'])
+        .filter_map(|segment| {
+            // Skip the environment variables set for the executable, e.g. `JAVA_HOME=... java`
+            segment.split_whitespace().find(|word| !word.contains('='))
+        })
+        .any(|executable| {
+            Path::new(executable)
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| commands.contains(&name))
+        })
+}
+
+fn get_java_tool_options() -> String {
+    match env::var("JAVA_TOOL_OPTIONS") {
+        Ok(options) if !options.is_empty() => format!("{} {}", options, JVM_PERF_MAP_OPTIONS),
+        _ => JVM_PERF_MAP_OPTIONS.into(),
+    }
+}
+
+/// Get the environment variables making the JIT runtimes used by the bench command write their
+/// perf maps to `/tmp/perf-<pid>.map`, where they are harvested after the run. Only the valgrind
+/// runs harvest perf maps, the walltime executor does not profile the benchmarks.
+pub fn get_jit_perf_map_envs(bench_command: &str) -> HashMap<&'static str, String> {
+    let mut envs = HashMap::new();
+    if is_command_using(bench_command, &JVM_COMMANDS) {
+        envs.insert("JAVA_TOOL_OPTIONS", get_java_tool_options());
+    }
+    if is_command_using(bench_command, &DOTNET_COMMANDS) {
+        envs.insert("DOTNET_PerfMapEnabled", "1".into());
+    }
+    envs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_command_using() {
+        assert!(is_command_using("java -jar benchmarks.jar", &JVM_COMMANDS));
+        assert!(is_command_using("./gradlew jmh", &JVM_COMMANDS));
+        assert!(is_command_using("cd bench && mvn verify", &JVM_COMMANDS));
+        assert!(!is_command_using("cargo-codspeed bench", &JVM_COMMANDS));
+        assert!(!is_command_using(
+            "pytest tests/ --codspeed --javascript",
+            &JVM_COMMANDS
+        ));
+        assert!(is_command_using(
+            "dotnet run -c Release --project Benchmarks",
+            &DOTNET_COMMANDS
+        ));
+        assert!(is_command_using(
+            "make build; JAVA_HOME=/opt/jdk17 java -jar benchmarks.jar | tee out.log",
+            &JVM_COMMANDS
+        ));
+        assert!(is_command_using(
+            "cargo build\n./mvnw verify",
+            &JVM_COMMANDS
+        ));
+        assert!(!is_command_using("echo dotnet", &DOTNET_COMMANDS));
+        assert!(!is_command_using(
+            "pytest --jvm=java tests/ || echo java",
+            &JVM_COMMANDS
+        ));
+    }
+
+    #[test]
+    fn test_get_jit_perf_map_envs() {
+        temp_env::with_var_unset("JAVA_TOOL_OPTIONS", || {
+            assert!(get_jit_perf_map_envs("pytest tests/").is_empty());
+            assert_eq!(
+                get_jit_perf_map_envs("java -jar benchmarks.jar"),
+                HashMap::from([("JAVA_TOOL_OPTIONS", JVM_PERF_MAP_OPTIONS.to_string())])
+            );
+            assert_eq!(
+                get_jit_perf_map_envs("dotnet run -c Release"),
+                HashMap::from([("DOTNET_PerfMapEnabled", "1".to_string())])
+            );
+        });
+        temp_env::with_var("JAVA_TOOL_OPTIONS", Some("-Xmx2g"), || {
+            assert_eq!(
+                get_jit_perf_map_envs("java -jar benchmarks.jar")["JAVA_TOOL_OPTIONS"],
+                format!("-Xmx2g {}", JVM_PERF_MAP_OPTIONS)
+            );
+        });
+    }
+}
@@ -1,4 +1,5 @@
 pub mod download_file;
 pub mod ignored_objects_path;
 pub mod introspected_nodejs;
+pub mod jit_perf_maps;
 pub mod perf_maps;
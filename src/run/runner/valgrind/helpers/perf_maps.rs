@@ -9,6 +9,9 @@ lazy_static! {
     static ref PERF_MAP_REGEX: Regex = Regex::new(r"perf-(\d+)\.map").unwrap();
 }
 
+/// Copy the perf maps written to `/tmp` by the benchmark processes into the profile folder. Any
+/// runtime writing one is covered, e.g. Ruby with `--yjit-perf` or PHP with
+/// `opcache.jit_debug=0x10`, but only the JVM and .NET ones are enabled by the runner.
 pub fn harvest_perf_maps(profile_folder: &Path) -> Result<()> {
     // Get profile files (files with .out extension)
     let profile_files = fs::read_dir(profile_folder)?
@@ -3,6 +3,7 @@ use crate::run::runner::helpers::get_bench_command::get_bench_command;
 use crate::run::runner::helpers::run_command_with_log_pipe::run_command_with_log_pipe;
 use crate::run::runner::valgrind::helpers::ignored_objects_path::get_objects_path_to_ignore;
 use crate::run::runner::valgrind::helpers::introspected_nodejs::setup_introspected_nodejs;
+use crate::run::runner::valgrind::helpers::jit_perf_maps::get_jit_perf_map_envs;
 use crate::run::{config::Config, instruments::mongo_tracer::MongoTracer};
 use lazy_static::lazy_static;
 use std::collections::HashMap;
@@ -83,7 +84,13 @@ pub fn measure(
         .arg(format!("--log-file={}", log_path.to_str().unwrap()).as_str());
 
     // Set the command to execute
-    cmd.args(["sh", "-c", get_bench_command(config)?.as_str()]);
+    let bench_command = get_bench_command(config)?;
+    let jit_perf_map_envs = get_jit_perf_map_envs(&bench_command);
+    if !jit_perf_map_envs.is_empty() {
+        debug!("Enabling the JIT perf maps: {:?}", jit_perf_map_envs);
+        cmd.envs(jit_perf_map_envs);
+    }
+    cmd.args(["sh", "-c", bench_command.as_str()]);
 
     // TODO: refactor and move this to the `Instruments` struct
     if let Some(mongo_tracer) = mongo_tracer {
@@ -91,8 +98,8 @@ pub fn measure(
     }
 
     debug!("cmd: {:?}", cmd);
-    let status = run_command_with_log_pipe(cmd)
-        .map_err(|e| anyhow!("failed to execute the benchmark process. {}", e))?;
+    let status = run_command_with_log_pipe(cmd, config.hang_timeout, false)
+        .context("failed to execute the benchmark process")?;
     if !status.success() {
         bail!("failed to execute the benchmark process");
     }
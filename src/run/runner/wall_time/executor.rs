@@ -3,17 +3,45 @@ use crate::prelude::*;
 use crate::run::instruments::mongo_tracer::MongoTracer;
 use crate::run::runner::executor::Executor;
 use crate::run::runner::helpers::get_bench_command::get_bench_command;
+use crate::run::runner::helpers::realtime::{check_rt_throttling, wrap_with_realtime_scheduling};
+use crate::run::runner::helpers::resource_limits::{
+    get_transient_cgroup_cmd_prefix, wrap_with_cgroup_stats_dump,
+};
 use crate::run::runner::helpers::run_command_with_log_pipe::run_command_with_log_pipe;
 use crate::run::runner::{ExecutorName, RunData};
-use crate::run::{check_system::SystemInfo, config::Config};
+use crate::run::{check_system::SystemInfo, config::Config, helpers::get_sudo_command};
 use async_trait::async_trait;
-use std::fs::canonicalize;
+use std::env::consts::ARCH;
+use std::fs::{canonicalize, create_dir_all};
+use std::path::Path;
 use std::process::Command;
 
 pub const WALL_TIME_RUNNER_MODE: &str = "walltime";
 
 pub struct WallTimeExecutor;
 
+/// Wrap the bench command with the switch to realtime scheduling and the dump of the cgroup
+/// statistics.
+///
+/// The realtime switch must come first: the stats dump runs the bench command in a subshell, in
+/// which `$$` is still the pid of the outer shell, so switching it from there would leave the
+/// benchmark under the default policy.
+fn wrap_bench_command(
+    bench_command: &str,
+    realtime_priority: Option<u8>,
+    sudo_command: &[&str],
+    cgroup_stats_dir: Option<&Path>,
+) -> String {
+    let mut bench_command = bench_command.to_string();
+    if let Some(cgroup_stats_dir) = cgroup_stats_dir {
+        bench_command = wrap_with_cgroup_stats_dump(&bench_command, cgroup_stats_dir);
+    }
+    if let Some(priority) = realtime_priority {
+        bench_command = wrap_with_realtime_scheduling(&bench_command, priority, sudo_command);
+    }
+    bench_command
+}
+
 #[async_trait(?Send)]
 impl Executor for WallTimeExecutor {
     fn name(&self) -> ExecutorName {
@@ -32,11 +60,54 @@ impl Executor for WallTimeExecutor {
     async fn run(
         &self,
         config: &Config,
-        _system_info: &SystemInfo,
+        system_info: &SystemInfo,
         run_data: &RunData,
         _mongo_tracer: &Option<MongoTracer>,
     ) -> Result<()> {
-        let mut cmd = Command::new("sh");
+        let uses_cgroup = config.resource_limits.is_some() || config.cgroup_accounting;
+        let sudo_command = if uses_cgroup || config.realtime_priority.is_some() {
+            get_sudo_command(system_info)?
+        } else {
+            vec![]
+        };
+        let mut cmd_args: Vec<String> = vec![];
+        if uses_cgroup {
+            cmd_args.extend(get_transient_cgroup_cmd_prefix(
+                config.resource_limits.as_ref(),
+                config.cgroup_accounting,
+                system_info,
+                &sudo_command,
+            ));
+        }
+        if config.disable_aslr {
+            cmd_args.extend(["setarch".into(), ARCH.into(), "-R".into()]);
+        }
+        if let Some(cpus) = &config.cpu_affinity {
+            cmd_args.extend(["taskset".into(), "--cpu-list".into(), cpus.iter().join(",")]);
+        }
+        if config.realtime_priority.is_some() {
+            check_rt_throttling()?;
+            if config.cpu_affinity.is_none() {
+                warn!("Running under SCHED_FIFO without --cpu-affinity, the benchmark can preempt the tasks of every CPU");
+            }
+        }
+        let cgroup_stats_dir = if config.cgroup_accounting {
+            let cgroup_stats_dir = run_data.profile_folder.join("cgroup");
+            create_dir_all(&cgroup_stats_dir)?;
+            Some(cgroup_stats_dir)
+        } else {
+            None
+        };
+        let bench_command = wrap_bench_command(
+            &get_bench_command(config)?,
+            config.realtime_priority,
+            &sudo_command,
+            cgroup_stats_dir.as_deref(),
+        );
+        cmd_args.extend(["sh".into(), "-c".into(), bench_command]);
+
+        let mut cmd = Command::new(&cmd_args[0]);
+        cmd.args(&cmd_args[1..]);
         cmd.envs(self.get_cmd_base_envs(&run_data.profile_folder));
 
         if let Some(cwd) = &config.working_directory {
@@ -44,11 +115,9 @@ impl Executor for WallTimeExecutor {
             cmd.current_dir(abs_cwd);
         }
 
-        cmd.args(["-c", get_bench_command(config)?.as_str()]);
-
         debug!("cmd: {:?}", cmd);
-        let status = run_command_with_log_pipe(cmd)
-            .map_err(|e| anyhow!("failed to execute the benchmark process. {}", e))?;
+        let status = run_command_with_log_pipe(cmd, config.hang_timeout, !sudo_command.is_empty())
+            .context("failed to execute the benchmark process")?;
         if !status.success() {
             bail!("failed to execute the benchmark process");
         }
@@ -65,3 +134,35 @@ impl Executor for WallTimeExecutor {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_bench_command_realtime_with_cgroup_stats() {
+        let cgroup_stats_dir = tempfile::tempdir().unwrap();
+        // Print the scheduling policy of the benchmark, 1 for SCHED_FIFO
+        let bench_command = wrap_bench_command(
+            "cut -d' ' -f41 /proc/self/stat",
+            Some(1),
+            &[],
+            Some(cgroup_stats_dir.path()),
+        );
+        assert!(bench_command.starts_with("chrt --fifo --pid 1 $$ || exit 1\n(\n"));
+
+        // Switching to SCHED_FIFO requires privileges
+        let can_use_realtime = Command::new("sh")
+            .args(["-c", "chrt --fifo --pid 1 $$"])
+            .status()
+            .is_ok_and(|status| status.success());
+        if can_use_realtime {
+            let output = Command::new("sh")
+                .args(["-c", &bench_command])
+                .output()
+                .unwrap();
+            assert!(output.status.success());
+            assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use std::fs::canonicalize;
+use std::io::Read;
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::prelude::*;
+use crate::run::config::ServiceConfig;
+use crate::run::interrupt::check_interrupted;
+
+const READY_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A long-running service, e.g. a server, running for the duration of the benchmarks.
+///
+/// The service runs in its own process group, so that every process it spawned is stopped with it.
+pub struct Service {
+    process: Option<Child>,
+}
+
+fn dump_service_log(mut stream: impl Read) -> Result<()> {
+    let mut buf = [0u8; 1024];
+    loop {
+        let num_read = stream.read(&mut buf)?;
+        if num_read == 0 {
+            break;
+        }
+        debug!("[service] {}", String::from_utf8_lossy(&buf[..num_read]));
+    }
+    Ok(())
+}
+
+fn shell_command(command: &str, working_directory: &Option<String>) -> Result<Command> {
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", command]);
+    if let Some(cwd) = working_directory {
+        cmd.current_dir(canonicalize(cwd)?);
+    }
+    Ok(cmd)
+}
+
+fn signal_process_group(pid: u32, signal: &str) -> Result<()> {
+    let status = Command::new("kill")
+        .args([signal, "--", &format!("-{}", pid)])
+        .stderr(Stdio::null())
+        .status()?;
+    if !status.success() {
+        bail!("Failed to send {} to the service", signal);
+    }
+    Ok(())
+}
+
+impl Service {
+    /// Start the service and wait for its ready check to succeed
+    pub fn start(
+        service_config: &ServiceConfig,
+        working_directory: &Option<String>,
+    ) -> Result<Self> {
+        let mut cmd = shell_command(&service_config.command, working_directory)?;
+        cmd.process_group(0)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        debug!("Starting the service: {:?}", cmd);
+        let mut process = cmd.spawn().context("Failed to start the service")?;
+
+        let stdout = process.stdout.take().expect("error taking child stdout");
+        let stderr = process.stderr.take().expect("error taking child stderr");
+        thread::spawn(move || dump_service_log(stdout));
+        thread::spawn(move || dump_service_log(stderr));
+
+        let mut service = Self {
+            process: Some(process),
+        };
+        if let Some(ready_check) = &service_config.ready_check {
+            service.wait_until_ready(
+                ready_check,
+                working_directory,
+                service_config.ready_timeout,
+            )?;
+        }
+        info!("Service started");
+
+        Ok(service)
+    }
+
+    fn wait_until_ready(
+        &mut self,
+        ready_check: &str,
+        working_directory: &Option<String>,
+        timeout: Duration,
+    ) -> Result<()> {
+        let start = Instant::now();
+        loop {
+            check_interrupted()?;
+            if let Some(status) = self.process.as_mut().and_then(|p| p.try_wait().ok()?) {
+                self.process = None;
+                bail!("The service exited before being ready: {}", status);
+            }
+
+            let ready = shell_command(ready_check, working_directory)?
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|status| status.success());
+            if ready {
+                return Ok(());
+            }
+            if start.elapsed() >= timeout {
+                bail!(
+                    "The service was not ready after {} seconds",
+                    timeout.as_secs()
+                );
+            }
+            thread::sleep(READY_CHECK_INTERVAL);
+        }
+    }
+
+    /// Stop the service, killing it if it does not exit after a SIGTERM
+    pub fn stop(&mut self) -> Result<()> {
+        let Some(mut process) = self.process.take() else {
+            return Ok(());
+        };
+        if process.try_wait()?.is_some() {
+            warn!("The service exited before the end of the benchmarks");
+            return Ok(());
+        }
+
+        debug!("Stopping the service");
+        signal_process_group(process.id(), "-TERM")?;
+        let start = Instant::now();
+        while process.try_wait()?.is_none() {
+            if start.elapsed() >= STOP_TIMEOUT {
+                warn!("The service did not stop after a SIGTERM, killing it");
+                signal_process_group(process.id(), "-KILL")?;
+                process.wait()?;
+                break;
+            }
+            thread::sleep(READY_CHECK_INTERVAL);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Service {
+    fn drop(&mut self) {
+        if let Err(e) = self.stop() {
+            warn!("Failed to stop the service: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service_config(command: &str, ready_check: Option<&str>) -> ServiceConfig {
+        ServiceConfig {
+            command: command.into(),
+            ready_check: ready_check.map(|check| check.into()),
+            ready_timeout: Duration::from_secs(5),
+        }
+    }
+
+    #[test]
+    fn test_start_and_stop() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let ready_file = tmp_dir.path().join("ready");
+        let mut service = Service::start(
+            &service_config(
+                &format!("sleep 0.2 && touch {} && sleep 60", ready_file.display()),
+                Some(&format!("test -f {}", ready_file.display())),
+            ),
+            &None,
+        )
+        .unwrap();
+        assert!(ready_file.exists());
+
+        service.stop().unwrap();
+        assert!(service.process.is_none());
+    }
+
+    #[test]
+    fn test_service_exits_before_ready() {
+        let result = Service::start(&service_config("exit 3", Some("false")), &None);
+        assert_eq!(
+            result.err().unwrap().to_string(),
+            "The service exited before being ready: exit status: 3"
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::prelude::*;
+use crate::run::check_system::CpuTuning;
+use crate::run::helpers::run_with_sudo;
+use crate::run::interrupt;
+
+const CPU_SYSFS_PATH: &str = "/sys/devices/system/cpu";
+const PERFORMANCE_GOVERNOR: &str = "performance";
+
+struct ChangedSetting {
+    path: PathBuf,
+    original_value: String,
+}
+
+/// Tunes the CPU settings of the system for more stable measurements.
+///
+/// Every changed setting is restored when the `SystemTuning` is dropped, or when a second signal
+/// forces the runner to exit.
+pub struct SystemTuning {
+    changed_settings: Arc<Mutex<Vec<ChangedSetting>>>,
+    pub cpu_tuning: CpuTuning,
+}
+
+fn read_setting(path: &Path) -> Option<String> {
+    fs::read_to_string(path)
+        .ok()
+        .map(|value| value.trim().to_string())
+}
+
+fn write_setting(path: &Path, value: &str) -> Result<()> {
+    run_with_sudo(&["sh", "-c", &format!("echo {} > {}", value, path.display())])
+}
+
+/// Restore the changed settings, in the reverse order they were applied. The settings that could
+/// not be restored are logged, so that they can be restored manually.
+fn restore_settings(changed_settings: &Mutex<Vec<ChangedSetting>>) -> Result<()> {
+    let mut changed_settings = changed_settings.lock().unwrap();
+    while let Some(setting) = changed_settings.last() {
+        debug!(
+            "Restoring {} to {}",
+            setting.path.display(),
+            setting.original_value
+        );
+        if let Err(e) = write_setting(&setting.path, &setting.original_value) {
+            warn!(
+                "The following system settings were left changed:\n{}",
+                changed_settings
+                    .iter()
+                    .rev()
+                    .map(|setting| format!(
+                        "  {}, originally {}",
+                        setting.path.display(),
+                        setting.original_value
+                    ))
+                    .join("\n")
+            );
+            return Err(e);
+        }
+        changed_settings.pop();
+    }
+    Ok(())
+}
+
+/// Get the paths of the `scaling_governor` files of every online CPU
+fn get_governor_paths(cpu_sysfs_path: &Path) -> Result<Vec<PathBuf>> {
+    let governor_paths = fs::read_dir(cpu_sysfs_path)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.strip_prefix("cpu"))
+                .is_some_and(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()))
+        })
+        .map(|entry| entry.path().join("cpufreq/scaling_governor"))
+        .filter(|path| path.exists())
+        .sorted()
+        .collect();
+    Ok(governor_paths)
+}
+
+impl SystemTuning {
+    /// Set the `performance` governor on all CPUs, disable turbo boost and SMT.
+    ///
+    /// Settings that are not available on the system are skipped with a warning.
+    pub fn apply() -> Result<Self> {
+        let cpu_sysfs_path = Path::new(CPU_SYSFS_PATH);
+        let mut system_tuning = SystemTuning {
+            changed_settings: Arc::new(Mutex::new(vec![])),
+            cpu_tuning: CpuTuning {
+                governor: None,
+                turbo_disabled: false,
+                smt_disabled: false,
+            },
+        };
+        let changed_settings = system_tuning.changed_settings.clone();
+        interrupt::on_forced_exit(move || {
+            if let Err(e) = restore_settings(&changed_settings) {
+                warn!("Failed to restore the system settings: {}", e);
+            }
+        });
+
+        let governor_paths = get_governor_paths(cpu_sysfs_path)?;
+        if governor_paths.is_empty() {
+            warn!("CPU frequency scaling is not available, skipping governor tuning");
+        } else {
+            for governor_path in governor_paths {
+                system_tuning.set(&governor_path, PERFORMANCE_GOVERNOR)?;
+            }
+            system_tuning.cpu_tuning.governor = Some(PERFORMANCE_GOVERNOR.into());
+        }
+
+        let intel_no_turbo_path = cpu_sysfs_path.join("intel_pstate/no_turbo");
+        let boost_path = cpu_sysfs_path.join("cpufreq/boost");
+        if intel_no_turbo_path.exists() {
+            system_tuning.set(&intel_no_turbo_path, "1")?;
+            system_tuning.cpu_tuning.turbo_disabled = true;
+        } else if boost_path.exists() {
+            system_tuning.set(&boost_path, "0")?;
+            system_tuning.cpu_tuning.turbo_disabled = true;
+        } else {
+            warn!("Turbo boost control is not available, skipping turbo tuning");
+        }
+
+        let smt_control_path = cpu_sysfs_path.join("smt/control");
+        match read_setting(&smt_control_path).as_deref() {
+            Some("on") => {
+                system_tuning.set(&smt_control_path, "off")?;
+                system_tuning.cpu_tuning.smt_disabled = true;
+            }
+            Some("off") | Some("forceoff") => system_tuning.cpu_tuning.smt_disabled = true,
+            _ => warn!("SMT control is not available, skipping SMT tuning"),
+        }
+
+        info!("System tuned: {:?}", system_tuning.cpu_tuning);
+        Ok(system_tuning)
+    }
+
+    fn set(&mut self, path: &Path, value: &str) -> Result<()> {
+        let original_value =
+            read_setting(path).ok_or_else(|| anyhow!("Failed to read {}", path.display()))?;
+        if original_value == value {
+            return Ok(());
+        }
+
+        debug!("Setting {} to {}", path.display(), value);
+        write_setting(path, value)?;
+        self.changed_settings.lock().unwrap().push(ChangedSetting {
+            path: path.to_path_buf(),
+            original_value,
+        });
+        Ok(())
+    }
+
+    /// Keep the changed settings after the run, instead of restoring them when dropped
+    pub fn keep(&mut self) {
+        info!("The system tuning will be kept after the run");
+        self.changed_settings.lock().unwrap().clear();
+    }
+
+    /// Restore the changed settings, in the reverse order they were applied
+    pub fn restore(&mut self) -> Result<()> {
+        restore_settings(&self.changed_settings)
+    }
+}
+
+impl Drop for SystemTuning {
+    fn drop(&mut self) {
+        if let Err(e) = self.restore() {
+            warn!("Failed to restore the system settings: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_governor_paths() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        for cpu in ["cpu0", "cpu1", "cpu2"] {
+            fs::create_dir_all(tmp_dir.path().join(cpu).join("cpufreq")).unwrap();
+            if cpu != "cpu2" {
+                fs::write(
+                    tmp_dir.path().join(cpu).join("cpufreq/scaling_governor"),
+                    "powersave\n",
+                )
+                .unwrap();
+            }
+        }
+        fs::create_dir_all(tmp_dir.path().join("cpufreq")).unwrap();
+        fs::create_dir_all(tmp_dir.path().join("cpuidle")).unwrap();
+
+        let governor_paths = get_governor_paths(tmp_dir.path()).unwrap();
+        assert_eq!(
+            governor_paths,
+            vec![
+                tmp_dir.path().join("cpu0/cpufreq/scaling_governor"),
+                tmp_dir.path().join("cpu1/cpufreq/scaling_governor"),
+            ]
+        );
+        assert_eq!(
+            read_setting(&governor_paths[0]),
+            Some("powersave".to_string())
+        );
+    }
+}
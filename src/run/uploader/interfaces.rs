@@ -1,12 +1,19 @@
+use std::str::FromStr;
+
 use serde::{Deserialize, Serialize};
 
+use crate::prelude::*;
+
 use crate::run::{
     check_system::SystemInfo,
     ci_provider::interfaces::{CIProviderMetadata, RepositoryProvider},
+    config::ResourceLimits,
     instruments::InstrumentName,
     runner::ExecutorName,
 };
 
+use super::ProfileEncoding;
+
 #[derive(Deserialize, Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct UploadMetadata {
@@ -14,6 +21,15 @@ pub struct UploadMetadata {
     pub version: Option<u32>,
     pub tokenless: bool,
     pub profile_md5: String,
+    /// Compression of the profile archive, omitted for the default gzip
+    #[serde(default, skip_serializing_if = "ProfileEncoding::is_gzip")]
+    pub profile_encoding: ProfileEncoding,
+    /// SHA-256 of the profile archive, encoded in hex
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile_sha256: Option<String>,
+    /// Group of the uploads merged into a single run, e.g. the shards of a sharded CI job
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_group: Option<RunGroup>,
     pub runner: Runner,
     pub platform: String,
     pub commit_hash: String,
@@ -21,6 +37,49 @@ pub struct UploadMetadata {
     pub ci_provider_metadata: CIProviderMetadata,
 }
 
+/// Shard of a run split across several CI jobs, parsed from `index/count`
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Shard {
+    /// 1-based index of the shard
+    pub index: u32,
+    pub count: u32,
+}
+
+impl FromStr for Shard {
+    type Err = Error;
+    fn from_str(shard: &str) -> Result<Self> {
+        let (index, count) = shard
+            .split_once('/')
+            .and_then(|(index, count)| Some((index.parse().ok()?, count.parse().ok()?)))
+            .ok_or_else(|| anyhow!("Invalid shard: {}, expected index/count", shard))?;
+        if index == 0 || index > count {
+            bail!(
+                "Invalid shard: {}, the index must be between 1 and the count",
+                shard
+            );
+        }
+        Ok(Self { index, count })
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RunGroup {
+    /// Key shared by the uploads of the group
+    pub key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shard: Option<Shard>,
+}
+
+impl RunGroup {
+    /// Whether the upload is a shard other than the last one of the group. Only the last shard
+    /// fetches the results of the merged run when running locally.
+    pub fn is_intermediate_shard(&self) -> bool {
+        self.shard.is_some_and(|shard| shard.index < shard.count)
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Runner {
@@ -28,6 +87,12 @@ pub struct Runner {
     pub version: String,
     pub instruments: Vec<InstrumentName>,
     pub executor: ExecutorName,
+    /// CPU cores the benchmark process was pinned to, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_affinity: Option<Vec<usize>>,
+    /// Resource limits enforced on the benchmark process, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resource_limits: Option<ResourceLimits>,
     #[serde(flatten)]
     pub system_info: SystemInfo,
 }
@@ -45,3 +110,28 @@ pub struct UploadData {
 pub struct UploadError {
     pub error: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_shard() {
+        assert_eq!(
+            "2/4".parse::<Shard>().unwrap(),
+            Shard { index: 2, count: 4 }
+        );
+        assert_eq!(
+            "0/4".parse::<Shard>().unwrap_err().to_string(),
+            "Invalid shard: 0/4, the index must be between 1 and the count"
+        );
+        assert_eq!(
+            "5/4".parse::<Shard>().unwrap_err().to_string(),
+            "Invalid shard: 5/4, the index must be between 1 and the count"
+        );
+        assert_eq!(
+            "2".parse::<Shard>().unwrap_err().to_string(),
+            "Invalid shard: 2, expected index/count"
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::prelude::*;
+use crate::{VALGRIND_CODSPEED_VERSION, VERSION};
+
+use super::profile_archive::ARCHIVE_SETTINGS_FILE_NAME;
+use super::upload_metadata::UPLOAD_METADATA_FILE_NAME;
+
+/// Name of the manifest file, at the root of the profile archive
+pub const MANIFEST_FILE_NAME: &str = "codspeed-manifest.json";
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestFile {
+    /// Path of the file, relative to the profile folder
+    pub path: String,
+    pub size: u64,
+    /// SHA-256 of the file, encoded in hex
+    pub sha256: String,
+}
+
+/// List of the files of the profile archive with their SHA-256, along with the versions of the
+/// tools that produced them
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveManifest {
+    pub tools: BTreeMap<String, String>,
+    pub files: Vec<ManifestFile>,
+}
+
+fn collect_files(
+    root: &Path,
+    folder: &Path,
+    excluded_files: &[PathBuf],
+    files: &mut Vec<ManifestFile>,
+) -> Result<()> {
+    for entry in fs::read_dir(folder)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(root, &path, excluded_files, files)?;
+            continue;
+        }
+        if excluded_files.contains(&path) {
+            continue;
+        }
+        let relative_path = path.strip_prefix(root)?.to_string_lossy().into_owned();
+        if [
+            MANIFEST_FILE_NAME,
+            UPLOAD_METADATA_FILE_NAME,
+            ARCHIVE_SETTINGS_FILE_NAME,
+        ]
+        .contains(&relative_path.as_str())
+        {
+            continue;
+        }
+        let content =
+            fs::read(&path).context(format!("Failed to read {} to hash it", path.display()))?;
+        files.push(ManifestFile {
+            path: relative_path,
+            size: content.len() as u64,
+            sha256: hex::encode(Sha256::digest(&content)),
+        });
+    }
+    Ok(())
+}
+
+impl ArchiveManifest {
+    /// Build the manifest of the profile folder, without the excluded files
+    pub fn create_excluding(profile_folder: &Path, excluded_files: &[PathBuf]) -> Result<Self> {
+        let mut files = vec![];
+        collect_files(profile_folder, profile_folder, excluded_files, &mut files)?;
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let mut tools = BTreeMap::from([("codspeed-runner".to_string(), VERSION.to_string())]);
+        // valgrind profiles are the `<pid>.out` files at the root of the profile folder
+        if files
+            .iter()
+            .any(|file| !file.path.contains('/') && file.path.ends_with(".out"))
+        {
+            tools.insert("valgrind".into(), VALGRIND_CODSPEED_VERSION.into());
+        }
+
+        Ok(Self { tools, files })
+    }
+
+    /// Check that the files of an extracted profile archive match the manifest
+    pub fn verify(&self, profile_folder: &Path) -> Result<()> {
+        for file in &self.files {
+            let content = fs::read(profile_folder.join(&file.path))
+                .context(format!("{} is missing", file.path))?;
+            if hex::encode(Sha256::digest(&content)) != file.sha256 {
+                bail!("{} does not match its SHA-256", file.path);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_and_verify() {
+        let profile_folder = tempfile::tempdir().unwrap();
+        fs::create_dir(profile_folder.path().join("results")).unwrap();
+        fs::write(profile_folder.path().join("results/1234.json"), "{}").unwrap();
+        fs::write(profile_folder.path().join("1234.out"), "profile").unwrap();
+
+        let manifest = ArchiveManifest::create_excluding(profile_folder.path(), &[]).unwrap();
+        assert_eq!(
+            manifest.tools.keys().collect_vec(),
+            vec!["codspeed-runner", "valgrind"]
+        );
+        assert_eq!(
+            manifest.files,
+            vec![
+                ManifestFile {
+                    path: "1234.out".into(),
+                    size: 7,
+                    sha256: "1900eab6c028483d7126599ee6f50de0d27907b5c65fa90524580b4b0f9852b0"
+                        .into(),
+                },
+                ManifestFile {
+                    path: "results/1234.json".into(),
+                    size: 2,
+                    sha256: "44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a"
+                        .into(),
+                },
+            ]
+        );
+        manifest.verify(profile_folder.path()).unwrap();
+
+        fs::write(profile_folder.path().join("1234.out"), "corrupted").unwrap();
+        assert_eq!(
+            manifest
+                .verify(profile_folder.path())
+                .unwrap_err()
+                .to_string(),
+            "1234.out does not match its SHA-256"
+        );
+    }
+}
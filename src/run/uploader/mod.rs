@@ -1,6 +1,18 @@
 mod interfaces;
+mod manifest;
+mod profile_archive;
+mod redaction;
+mod s3;
+mod size_budget;
 mod upload;
 mod upload_metadata;
 
 pub use interfaces::*;
-pub use upload::upload;
+pub use profile_archive::{
+    ArchiveSettings, ProfileArchive, ProfileCompression, ProfileEncoding,
+    ARCHIVE_SETTINGS_FILE_NAME,
+};
+pub use redaction::Redaction;
+pub use s3::{upload_to_s3, S3Target};
+pub use upload::{create_profile_archive, prepare_upload, upload, upload_archive};
+pub use upload_metadata::UPLOAD_METADATA_FILE_NAME;
@@ -0,0 +1,294 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use async_compression::tokio::bufread::{GzipDecoder, ZstdDecoder};
+use async_compression::tokio::write::{GzipEncoder, ZstdEncoder};
+use async_compression::zstd::CParameter;
+use async_compression::Level;
+use base64::{engine::general_purpose, Engine as _};
+use bytes::Bytes;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio_tar::{Archive, Builder, Header};
+
+use crate::prelude::*;
+
+use super::manifest::{ArchiveManifest, MANIFEST_FILE_NAME};
+use super::redaction::Redaction;
+use super::upload_metadata::UPLOAD_METADATA_FILE_NAME;
+
+/// Name of the file persisting the archive settings of a run in its profile folder, to archive it
+/// again with `codspeed upload --profile-folder`
+pub const ARCHIVE_SETTINGS_FILE_NAME: &str = "archive-settings.json";
+
+/// Compression format of the profile archive
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProfileEncoding {
+    #[default]
+    Gzip,
+    Zstd,
+}
+
+impl ProfileEncoding {
+    pub fn is_gzip(&self) -> bool {
+        *self == ProfileEncoding::Gzip
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ProfileEncoding::Gzip => "application/gzip",
+            ProfileEncoding::Zstd => "application/zstd",
+        }
+    }
+
+    /// Name of the archive file when stored outside of the upload, e.g. in a bundle
+    pub fn archive_file_name(&self) -> &'static str {
+        match self {
+            ProfileEncoding::Gzip => "profile.tar.gz",
+            ProfileEncoding::Zstd => "profile.tar.zst",
+        }
+    }
+}
+
+/// Compression settings of the profile archive
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileCompression {
+    pub encoding: ProfileEncoding,
+    pub level: Option<i32>,
+    /// Number of compression threads, only supported by zstd
+    pub threads: Option<u32>,
+}
+
+/// Settings used to create the profile archive of a run
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveSettings {
+    pub compression: ProfileCompression,
+    /// Maximum size of the archive in bytes, artifacts are left out of it to fit
+    pub max_size: Option<u64>,
+    pub redaction: Redaction,
+}
+
+/// The compressed archive of the profile folder, uploaded along with its md5 hash
+pub struct ProfileArchive {
+    pub buffer: Bytes,
+    pub encoding: ProfileEncoding,
+    /// md5 hash of the archive, encoded in base64
+    pub hash: String,
+    /// SHA-256 of the archive, encoded in hex
+    pub sha256: String,
+    /// Manifest of the archive, unknown for an archive read back from a buffer
+    pub manifest: Option<ArchiveManifest>,
+    /// Files of the profile folder left out of the archive, relative to the profile folder
+    pub excluded_files: Vec<String>,
+}
+
+/// Compress with a zstd worker per available CPU by default, the archives of big walltime runs can
+/// take minutes to compress on a single thread
+fn get_default_compression_threads() -> Option<u32> {
+    std::thread::available_parallelism()
+        .ok()
+        .map(|cpus| cpus.get() as u32)
+        .filter(|&cpus| cpus > 1)
+}
+
+/// Recursively append the content of `folder` to the tar, under `archive_folder`, without the
+/// excluded files
+async fn append_folder<W: AsyncWrite + Unpin + Send + Sync + 'static>(
+    tar: &mut Builder<W>,
+    folder: &Path,
+    archive_folder: &Path,
+    excluded_files: &[PathBuf],
+) -> Result<()> {
+    for entry in fs::read_dir(folder)? {
+        let entry = entry?;
+        let path = entry.path();
+        let archive_path = archive_folder.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            tar.append_dir(&archive_path, &path).await?;
+            Box::pin(append_folder(tar, &path, &archive_path, excluded_files)).await?;
+        } else if !excluded_files.contains(&path) {
+            tar.append_path_with_name(&path, &archive_path).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Write a tar archive of the profile folder and its manifest to the encoder, without the upload
+/// metadata and the archive settings persisted in the profile folder, and the excluded files
+async fn write_tar<E: AsyncWrite + Unpin + Send + Sync + 'static>(
+    encoder: E,
+    profile_folder: &Path,
+    manifest: &ArchiveManifest,
+    excluded_files: &[PathBuf],
+) -> Result<E> {
+    let manifest = serde_json::to_vec_pretty(manifest)?;
+    let mut excluded_files = excluded_files.to_vec();
+    excluded_files.push(profile_folder.join(UPLOAD_METADATA_FILE_NAME));
+    excluded_files.push(profile_folder.join(ARCHIVE_SETTINGS_FILE_NAME));
+    let mut tar = Builder::new(encoder);
+    tar.append_dir(".", profile_folder).await?;
+    append_folder(&mut tar, profile_folder, Path::new("."), &excluded_files).await?;
+    let mut header = Header::new_gnu();
+    header.set_size(manifest.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    tar.append_data(&mut header, MANIFEST_FILE_NAME, manifest.as_slice())
+        .await?;
+    let mut encoder = tar.into_inner().await?;
+    encoder.shutdown().await?;
+    Ok(encoder)
+}
+
+impl ProfileArchive {
+    /// Create a compressed tar archive of the profile folder
+    pub async fn create(profile_folder: &Path, compression: &ProfileCompression) -> Result<Self> {
+        Self::create_excluding(profile_folder, compression, &[]).await
+    }
+
+    /// Create a compressed tar archive of the profile folder, without the excluded files
+    pub async fn create_excluding(
+        profile_folder: &Path,
+        compression: &ProfileCompression,
+        excluded_files: &[PathBuf],
+    ) -> Result<Self> {
+        let level = compression
+            .level
+            .map(Level::Precise)
+            .unwrap_or(Level::Default);
+        let manifest = ArchiveManifest::create_excluding(profile_folder, excluded_files)?;
+        let buffer = match compression.encoding {
+            ProfileEncoding::Gzip => write_tar(
+                GzipEncoder::with_quality(Vec::new(), level),
+                profile_folder,
+                &manifest,
+                excluded_files,
+            )
+            .await?
+            .into_inner(),
+            ProfileEncoding::Zstd => {
+                let params = compression
+                    .threads
+                    .or_else(get_default_compression_threads)
+                    .map(|threads| vec![CParameter::nb_workers(threads)])
+                    .unwrap_or_default();
+                write_tar(
+                    ZstdEncoder::with_quality_and_params(Vec::new(), level, &params),
+                    profile_folder,
+                    &manifest,
+                    excluded_files,
+                )
+                .await?
+                .into_inner()
+            }
+        };
+
+        Ok(Self {
+            manifest: Some(manifest),
+            excluded_files: excluded_files
+                .iter()
+                .map(|path| {
+                    path.strip_prefix(profile_folder)
+                        .unwrap_or(path)
+                        .to_string_lossy()
+                        .into_owned()
+                })
+                .collect(),
+            ..Self::from_buffer(buffer, compression.encoding)
+        })
+    }
+
+    pub fn from_buffer(buffer: Vec<u8>, encoding: ProfileEncoding) -> Self {
+        let archive_digest = md5::compute(buffer.as_slice());
+        let hash = general_purpose::STANDARD.encode(archive_digest.0);
+        let sha256 = hex::encode(Sha256::digest(&buffer));
+        Self {
+            buffer: buffer.into(),
+            encoding,
+            hash,
+            sha256,
+            manifest: None,
+            excluded_files: vec![],
+        }
+    }
+
+    /// Extract the archive and check its files against its manifest, to detect a corrupted
+    /// archive read back from the disk before uploading it. Archives without a manifest, written
+    /// by older versions of the runner, are not checked.
+    pub async fn verify(&self) -> Result<()> {
+        let extract_dir = tempfile::tempdir()?;
+        let decoder: Box<dyn AsyncRead + Unpin + Send + Sync> = match self.encoding {
+            ProfileEncoding::Gzip => Box::new(GzipDecoder::new(&self.buffer[..])),
+            ProfileEncoding::Zstd => Box::new(ZstdDecoder::new(&self.buffer[..])),
+        };
+        Archive::new(decoder)
+            .unpack(extract_dir.path())
+            .await
+            .context("The profile archive is corrupted, failed to extract it")?;
+
+        let manifest_path = extract_dir.path().join(MANIFEST_FILE_NAME);
+        if !manifest_path.exists() {
+            debug!("The profile archive has no manifest, skipping its verification");
+            return Ok(());
+        }
+        let manifest: ArchiveManifest = serde_json::from_slice(&fs::read(manifest_path)?)
+            .context("The profile archive is corrupted, failed to parse its manifest")?;
+        manifest
+            .verify(extract_dir.path())
+            .context("The profile archive is corrupted")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_buffer() {
+        let archive = ProfileArchive::from_buffer(b"profile".to_vec(), ProfileEncoding::Gzip);
+        assert_eq!(archive.hash, "fZdIGx/mb0tR25DafnlNnw==");
+        assert_eq!(
+            archive.sha256,
+            "1900eab6c028483d7126599ee6f50de0d27907b5c65fa90524580b4b0f9852b0"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create() {
+        let profile_folder = tempfile::tempdir().unwrap();
+        std::fs::write(profile_folder.path().join("1234.out"), "data".repeat(1000)).unwrap();
+
+        let gzip_archive = ProfileArchive::create(profile_folder.path(), &Default::default())
+            .await
+            .unwrap();
+        assert_eq!(&gzip_archive.buffer[..2], &[0x1f, 0x8b]);
+        gzip_archive.verify().await.unwrap();
+
+        let zstd_archive = ProfileArchive::create(
+            profile_folder.path(),
+            &ProfileCompression {
+                encoding: ProfileEncoding::Zstd,
+                level: Some(19),
+                threads: Some(2),
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(zstd_archive.encoding, ProfileEncoding::Zstd);
+        assert_eq!(&zstd_archive.buffer[..4], &[0x28, 0xb5, 0x2f, 0xfd]);
+        zstd_archive.verify().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_verify_corrupted() {
+        let archive = ProfileArchive::from_buffer(b"profile".to_vec(), ProfileEncoding::Gzip);
+        assert_eq!(
+            archive.verify().await.unwrap_err().to_string(),
+            "The profile archive is corrupted, failed to extract it"
+        );
+    }
+}
@@ -0,0 +1,275 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tempfile::TempDir;
+
+use crate::prelude::*;
+
+/// Redaction of the artifacts of the profile folder, applied before the profile archive is
+/// created, for the companies that must not let internal paths or symbols leave their network
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Redaction {
+    /// Path prefixes stripped from the perf maps, the callgrind profiles and the logs
+    pub path_prefixes: Vec<String>,
+    /// Replace the symbols of the perf maps and the callgrind profiles with their hash, and drop
+    /// the debug info files
+    pub hash_symbols: bool,
+    /// Environment variables whose values are replaced with their name in the logs
+    pub env_vars: Vec<String>,
+}
+
+fn is_perf_map(file_name: &str) -> bool {
+    file_name.starts_with("perf-") && file_name.ends_with(".map")
+}
+
+fn is_callgrind_profile(file_name: &str) -> bool {
+    file_name.ends_with(".out")
+}
+
+fn is_log(file_name: &str) -> bool {
+    file_name.ends_with(".log")
+}
+
+fn is_debug_info(file_name: &str) -> bool {
+    file_name.ends_with(".debug") || file_name.ends_with(".dwp")
+}
+
+fn hash_symbol(symbol: &str) -> String {
+    format!("sym_{}", &hex::encode(Sha256::digest(symbol))[..16])
+}
+
+/// Hash the name of a callgrind `fn=`/`cfn=` entry, keeping its compressed id if any: `(12) name`
+/// defines the id 12, while a bare `(12)` refers to an already defined name
+fn hash_callgrind_function(function: &str) -> String {
+    match function
+        .strip_prefix('(')
+        .and_then(|rest| rest.split_once(')'))
+    {
+        Some((id, "")) => format!("({})", id),
+        Some((id, name)) => format!("({}) {}", id, hash_symbol(name.trim_start())),
+        None => hash_symbol(function),
+    }
+}
+
+impl Redaction {
+    pub fn is_enabled(&self) -> bool {
+        !self.path_prefixes.is_empty() || self.hash_symbols || !self.env_vars.is_empty()
+    }
+
+    fn strip_path_prefixes(&self, content: String) -> String {
+        self.path_prefixes
+            .iter()
+            .fold(content, |content, prefix| content.replace(prefix, ""))
+    }
+
+    /// Redact a perf map, made of `<start> <size> <symbol>` lines
+    fn redact_perf_map(&self, content: String) -> String {
+        let content = self.strip_path_prefixes(content);
+        if !self.hash_symbols {
+            return content;
+        }
+        content
+            .lines()
+            .map(|line| match line.splitn(3, ' ').collect_vec()[..] {
+                [start, size, symbol] => format!("{} {} {}\n", start, size, hash_symbol(symbol)),
+                _ => format!("{}\n", line),
+            })
+            .collect()
+    }
+
+    /// Redact a callgrind profile: the path prefixes are stripped from the `fl=`, `fi=`, `fe=`,
+    /// `cfi=` and `ob=` file entries, and the `fn=`/`cfn=` function names are hashed
+    fn redact_callgrind_profile(&self, content: String) -> String {
+        let content = self.strip_path_prefixes(content);
+        if !self.hash_symbols {
+            return content;
+        }
+        content
+            .lines()
+            .map(|line| {
+                let redacted_line = ["fn=", "cfn="].iter().find_map(|key| {
+                    line.strip_prefix(key)
+                        .map(|function| format!("{}{}", key, hash_callgrind_function(function)))
+                });
+                format!("{}\n", redacted_line.as_deref().unwrap_or(line))
+            })
+            .collect()
+    }
+
+    fn redact_log(&self, content: String) -> String {
+        let content = self.strip_path_prefixes(content);
+        self.env_vars
+            .iter()
+            .filter_map(|name| {
+                env::var(name)
+                    .ok()
+                    .filter(|value| !value.is_empty())
+                    .map(|value| (name, value))
+            })
+            .fold(content, |content, (name, value)| {
+                content.replace(&value, &format!("${}", name))
+            })
+    }
+
+    /// Copy the profile folder into a staging folder, redacting the perf maps, the callgrind
+    /// profiles and the logs, and leaving out the debug info files when hashing the symbols. The
+    /// profile folder itself is left untouched, to keep the local data usable.
+    pub fn redact_profile_folder(&self, profile_folder: &Path) -> Result<TempDir> {
+        let staging_folder = tempfile::tempdir()?;
+        self.redact_folder(profile_folder, staging_folder.path())?;
+        Ok(staging_folder)
+    }
+
+    fn redact_folder(&self, source: &Path, destination: &Path) -> Result<()> {
+        fs::create_dir_all(destination)?;
+        for entry in fs::read_dir(source)? {
+            let path = entry?.path();
+            let destination_path = destination.join(path.file_name().unwrap());
+            if path.is_dir() {
+                self.redact_folder(&path, &destination_path)?;
+                continue;
+            }
+            let file_name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or("");
+            if self.hash_symbols && is_debug_info(file_name) {
+                debug!("Leaving out {} to redact its symbols", path.display());
+                continue;
+            }
+            let redacted = if is_perf_map(file_name) {
+                self.redact_perf_map(fs::read_to_string(&path)?)
+            } else if is_callgrind_profile(file_name) {
+                self.redact_callgrind_profile(fs::read_to_string(&path)?)
+            } else if is_log(file_name) {
+                self.redact_log(fs::read_to_string(&path)?)
+            } else {
+                fs::copy(&path, &destination_path)
+                    .context(format!("Failed to copy {}", path.display()))?;
+                continue;
+            };
+            fs::write(&destination_path, redacted)
+                .context(format!("Failed to redact {}", path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_profile_folder() {
+        let profile_folder = tempfile::tempdir().unwrap();
+        fs::write(
+            profile_folder.path().join("perf-1234.map"),
+            "7f1c 20 /home/alice/acme/src/lib.rs::acme::compute\n7f3c 10\n",
+        )
+        .unwrap();
+        fs::write(profile_folder.path().join("libacme.so.debug"), "debug").unwrap();
+        fs::write(
+            profile_folder.path().join("runner.log"),
+            "Running in /home/alice/acme/benches with token s3cr3t",
+        )
+        .unwrap();
+        fs::create_dir(profile_folder.path().join("results")).unwrap();
+        fs::write(
+            profile_folder.path().join("results/1234.json"),
+            "/home/alice/acme",
+        )
+        .unwrap();
+
+        let redacted_folder = temp_env::with_var("ACME_TOKEN", Some("s3cr3t"), || {
+            Redaction {
+                path_prefixes: vec!["/home/alice/acme/".into()],
+                hash_symbols: true,
+                env_vars: vec!["ACME_TOKEN".into(), "ACME_UNSET".into()],
+            }
+            .redact_profile_folder(profile_folder.path())
+            .unwrap()
+        });
+
+        assert_eq!(
+            fs::read_to_string(redacted_folder.path().join("perf-1234.map")).unwrap(),
+            format!(
+                "7f1c 20 {}\n7f3c 10\n",
+                hash_symbol("src/lib.rs::acme::compute")
+            )
+        );
+        assert!(!redacted_folder.path().join("libacme.so.debug").exists());
+        assert_eq!(
+            fs::read_to_string(redacted_folder.path().join("runner.log")).unwrap(),
+            "Running in benches with token $ACME_TOKEN"
+        );
+        assert_eq!(
+            fs::read_to_string(redacted_folder.path().join("results/1234.json")).unwrap(),
+            "/home/alice/acme"
+        );
+        // The profile folder itself is left untouched
+        assert!(profile_folder.path().join("libacme.so.debug").exists());
+        assert_eq!(
+            fs::read_to_string(profile_folder.path().join("runner.log")).unwrap(),
+            "Running in /home/alice/acme/benches with token s3cr3t"
+        );
+    }
+
+    #[test]
+    fn test_redact_callgrind_profile() {
+        let profile_folder = tempfile::tempdir().unwrap();
+        fs::write(
+            profile_folder.path().join("1234.out"),
+            include_str!("fixtures/callgrind.out"),
+        )
+        .unwrap();
+
+        let redacted_folder = Redaction {
+            path_prefixes: vec!["/home/alice/acme/".into()],
+            hash_symbols: true,
+            env_vars: vec![],
+        }
+        .redact_profile_folder(profile_folder.path())
+        .unwrap();
+
+        let redacted = fs::read_to_string(redacted_folder.path().join("1234.out")).unwrap();
+        assert!(!redacted.contains("/home/alice/acme/"));
+        assert!(!redacted.contains("acme::compute"));
+        assert!(!redacted.contains("acme::helper"));
+        let compute = hash_symbol("acme::compute");
+        let helper = hash_symbol("acme::helper");
+        assert_eq!(
+            redacted.lines().collect_vec(),
+            vec![
+                "# callgrind format",
+                "version: 1",
+                "creator: callgrind-3.22.0",
+                "cmd: target/release/deps/bench",
+                "positions: line",
+                "events: Ir",
+                "",
+                "ob=(1) target/release/deps/bench",
+                "fl=(1) src/lib.rs",
+                &format!("fn=(1) {}", compute),
+                "3 12",
+                "fi=(2) src/helper.rs",
+                "8 4",
+                "fe=(1)",
+                "4 2",
+                "cfi=(2)",
+                &format!("cfn=(2) {}", helper),
+                "calls=1 8",
+                "5 20",
+                "",
+                "fl=(2)",
+                "fn=(2)",
+                "8 20",
+                "",
+                "totals: 58",
+            ]
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use indicatif::HumanBytes;
+
+use crate::prelude::*;
+
+use super::profile_archive::{ProfileArchive, ProfileCompression};
+
+/// Artifacts of the profile folder that can be dropped when the profile archive exceeds its size
+/// budget. The results, e.g. the walltime results or the valgrind profiles, are never dropped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DroppableArtifact {
+    /// Perf maps and separate debug info files, only used to symbolize the profiles
+    DebugInfo,
+    /// Raw perf data, only used to build the flamegraphs
+    PerfData,
+}
+
+impl DroppableArtifact {
+    /// Order in which the artifacts are dropped, from the least to the most useful
+    const DROP_ORDER: [DroppableArtifact; 2] =
+        [DroppableArtifact::DebugInfo, DroppableArtifact::PerfData];
+
+    fn matches(&self, file_name: &str) -> bool {
+        match self {
+            DroppableArtifact::DebugInfo => {
+                (file_name.starts_with("perf-") && file_name.ends_with(".map"))
+                    || file_name.ends_with(".debug")
+                    || file_name.ends_with(".dwp")
+            }
+            DroppableArtifact::PerfData => {
+                file_name.starts_with("perf.data")
+                    || file_name.ends_with(".perf")
+                    || file_name.ends_with(".pipedata")
+            }
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            DroppableArtifact::DebugInfo => "debug info",
+            DroppableArtifact::PerfData => "raw perf data",
+        }
+    }
+}
+
+/// Recursively find the files of the profile folder matching the artifact
+fn find_artifact_files(folder: &Path, artifact: DroppableArtifact) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for entry in fs::read_dir(folder)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(find_artifact_files(&path, artifact)?);
+        } else if path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| artifact.matches(name))
+        {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+/// Create the profile archive, leaving artifacts out of it in the order of
+/// [`DroppableArtifact::DROP_ORDER`] while it exceeds `max_size` bytes. The profile folder itself
+/// is left untouched.
+pub async fn create_archive_within_budget(
+    profile_folder: &Path,
+    compression: &ProfileCompression,
+    max_size: Option<u64>,
+) -> Result<ProfileArchive> {
+    let mut archive = ProfileArchive::create(profile_folder, compression).await?;
+    let Some(max_size) = max_size else {
+        return Ok(archive);
+    };
+
+    let mut excluded_files = vec![];
+    for artifact in DroppableArtifact::DROP_ORDER {
+        if archive.buffer.len() as u64 <= max_size {
+            return Ok(archive);
+        }
+        let files = find_artifact_files(profile_folder, artifact)?;
+        if files.is_empty() {
+            continue;
+        }
+        warn!(
+            "The profile archive ({}) exceeds the maximum archive size ({}), leaving the {} ({} files) out of it",
+            HumanBytes(archive.buffer.len() as u64),
+            HumanBytes(max_size),
+            artifact.description(),
+            files.len()
+        );
+        excluded_files.extend(files);
+        archive =
+            ProfileArchive::create_excluding(profile_folder, compression, &excluded_files).await?;
+    }
+
+    if archive.buffer.len() as u64 > max_size {
+        bail!(
+            "The profile archive ({}) exceeds the maximum archive size ({}), even without the droppable artifacts",
+            HumanBytes(archive.buffer.len() as u64),
+            HumanBytes(max_size)
+        );
+    }
+    Ok(archive)
+}
+
+#[cfg(test)]
+mod tests {
+    use async_compression::tokio::bufread::GzipDecoder;
+    use rand::RngCore;
+    use tokio_tar::Archive;
+
+    use super::*;
+
+    #[test]
+    fn test_droppable_artifact_matches() {
+        assert!(DroppableArtifact::DebugInfo.matches("perf-1234.map"));
+        assert!(DroppableArtifact::DebugInfo.matches("libfoo.so.debug"));
+        assert!(DroppableArtifact::PerfData.matches("perf.data"));
+        assert!(DroppableArtifact::PerfData.matches("1234.pipedata"));
+        for artifact in DroppableArtifact::DROP_ORDER {
+            assert!(!artifact.matches("1234.out"));
+            assert!(!artifact.matches("results.json"));
+        }
+    }
+
+    fn write_random_file(path: &Path, size: usize) {
+        let mut content = vec![0; size];
+        rand::thread_rng().fill_bytes(&mut content);
+        fs::write(path, content).unwrap();
+    }
+
+    /// Extract the archive, to check the files it contains
+    async fn extract(archive: &ProfileArchive) -> tempfile::TempDir {
+        let extract_dir = tempfile::tempdir().unwrap();
+        Archive::new(GzipDecoder::new(&archive.buffer[..]))
+            .unpack(extract_dir.path())
+            .await
+            .unwrap();
+        extract_dir
+    }
+
+    #[tokio::test]
+    async fn test_create_archive_within_budget() {
+        let profile_folder = tempfile::tempdir().unwrap();
+        let results_folder = profile_folder.path().join("results");
+        fs::create_dir(&results_folder).unwrap();
+        write_random_file(&results_folder.join("1234.json"), 1000);
+        write_random_file(&profile_folder.path().join("perf-1234.map"), 20_000);
+        write_random_file(&profile_folder.path().join("1234.perf"), 20_000);
+
+        let archive =
+            create_archive_within_budget(profile_folder.path(), &Default::default(), Some(30_000))
+                .await
+                .unwrap();
+        archive.verify().await.unwrap();
+        assert_eq!(archive.excluded_files, vec!["perf-1234.map".to_string()]);
+        let extract_dir = extract(&archive).await;
+        assert!(!extract_dir.path().join("perf-1234.map").exists());
+        assert!(extract_dir.path().join("1234.perf").exists());
+
+        let archive =
+            create_archive_within_budget(profile_folder.path(), &Default::default(), Some(5_000))
+                .await
+                .unwrap();
+        archive.verify().await.unwrap();
+        let extract_dir = extract(&archive).await;
+        assert!(!extract_dir.path().join("1234.perf").exists());
+        assert!(extract_dir.path().join("results/1234.json").exists());
+
+        assert!(create_archive_within_budget(
+            profile_folder.path(),
+            &Default::default(),
+            Some(100)
+        )
+        .await
+        .is_err());
+
+        // The artifacts are only left out of the archive, the profile folder is untouched
+        assert!(profile_folder.path().join("perf-1234.map").exists());
+        assert!(profile_folder.path().join("1234.perf").exists());
+        assert!(results_folder.join("1234.json").exists());
+    }
+}
@@ -3,41 +3,31 @@ use crate::run::{
     check_system::SystemInfo, ci_provider::CIProvider, config::Config, runner::RunData,
     uploader::UploadError,
 };
-use crate::{prelude::*, request_client::REQUEST_CLIENT};
-use async_compression::tokio::write::GzipEncoder;
-use base64::{engine::general_purpose, Engine as _};
+use crate::{
+    prelude::*,
+    request_client::{put_upload, send_upload_with_retry, REQUEST_CLIENT},
+};
 use console::style;
 use reqwest::StatusCode;
-use tokio::io::AsyncWriteExt;
-use tokio_tar::Builder;
+use std::fs;
+use std::path::Path;
+use url::Url;
 
 use super::interfaces::{UploadData, UploadMetadata};
-
-/// Create a tar.gz archive buffer of the profile folder and return its md5 hash encoded in base64
-async fn get_profile_archive_buffer(run_data: &RunData) -> Result<(Vec<u8>, String)> {
-    let enc = GzipEncoder::new(Vec::new());
-    let mut tar = Builder::new(enc);
-    tar.append_dir_all(".", run_data.profile_folder.clone())
-        .await?;
-    let mut gzip_encoder = tar.into_inner().await?;
-    gzip_encoder.shutdown().await?;
-
-    let archive_buffer = gzip_encoder.into_inner();
-    let archive_digest = md5::compute(archive_buffer.as_slice());
-    let archive_hash = general_purpose::STANDARD.encode(archive_digest.0);
-
-    Ok((archive_buffer, archive_hash))
-}
+use super::profile_archive::{ArchiveSettings, ProfileArchive, ARCHIVE_SETTINGS_FILE_NAME};
+use super::size_budget::create_archive_within_budget;
+use super::upload_metadata::UPLOAD_METADATA_FILE_NAME;
 
 async fn retrieve_upload_data(
-    config: &Config,
+    upload_url: &Url,
+    token: Option<&String>,
     upload_metadata: &UploadMetadata,
 ) -> Result<UploadData> {
     let mut upload_request = REQUEST_CLIENT
-        .post(config.upload_url.clone())
+        .post(upload_url.clone())
         .json(&upload_metadata);
-    if !upload_metadata.tokenless {
-        upload_request = upload_request.header("Authorization", config.token.clone().unwrap());
+    if let Some(token) = token.filter(|_| !upload_metadata.tokenless) {
+        upload_request = upload_request.header("Authorization", token);
     }
 
     let response = upload_request.send().await;
@@ -57,6 +47,10 @@ async fn retrieve_upload_data(
                         "Check that CODSPEED_TOKEN is set and has the correct value"
                     };
                     error_message.push_str(&format!("\n\n{}", additional_message));
+                } else if upload_metadata.platform == "ci-metadata-file" {
+                    error_message.push_str(
+                        "\n\nThe upload endpoint may not accept the runs described by a CI metadata file yet",
+                    );
                 }
                 bail!(
                     "Failed to retrieve upload data: {}\n  -> {} {}",
@@ -69,46 +63,111 @@ async fn retrieve_upload_data(
 
             Ok(response.json().await?)
         }
-        Err(err) => Err(err.into()),
+        Err(err) => Err(anyhow!(err).context(
+            "Failed to reach the upload endpoint. Behind a proxy, set the HTTPS_PROXY environment variable, and use --ca-bundle if it intercepts TLS",
+        )),
     }
 }
 
 async fn upload_archive_buffer(
     upload_data: &UploadData,
-    archive_buffer: Vec<u8>,
-    archive_hash: &String,
+    archive: &ProfileArchive,
+    rate_limit: Option<u64>,
 ) -> Result<()> {
-    REQUEST_CLIENT
-        .put(upload_data.upload_url.clone())
-        .header("Content-Type", "application/gzip")
-        .header("Content-Length", archive_buffer.len())
-        .header("Content-MD5", archive_hash)
-        .body(archive_buffer)
-        .send()
-        .await?;
+    let response = send_upload_with_retry(|| {
+        put_upload(
+            upload_data.upload_url.clone(),
+            archive.buffer.clone(),
+            rate_limit,
+        )
+        .header("Content-Type", archive.encoding.content_type())
+        .header("Content-Length", archive.buffer.len())
+        .header("Content-MD5", &archive.hash)
+    })
+    .await
+    .context("Failed to upload the performance data")?;
+    if !response.status().is_success() {
+        bail!(
+            "Failed to upload the performance data: {} {}",
+            response.status(),
+            response.text().await.unwrap_or_default()
+        );
+    }
 
     Ok(())
 }
 
 pub struct UploadResult {
     pub run_id: String,
+    pub archive_size: usize,
+}
+
+/// Create the profile archive of a profile folder, redacted and within its size budget
+pub async fn create_profile_archive(
+    profile_folder: &Path,
+    settings: &ArchiveSettings,
+) -> Result<ProfileArchive> {
+    let redacted_profile_folder = if settings.redaction.is_enabled() {
+        info!("Redacting the profile folder");
+        Some(settings.redaction.redact_profile_folder(profile_folder)?)
+    } else {
+        None
+    };
+    create_archive_within_budget(
+        redacted_profile_folder
+            .as_ref()
+            .map_or(profile_folder, |folder| folder.path()),
+        &settings.compression,
+        settings.max_size,
+    )
+    .await
 }
 
+/// Create the profile archive of the run and its upload metadata. The upload metadata and the
+/// archive settings are persisted in the profile folder, to upload it again with
+/// `codspeed upload --profile-folder`.
 #[allow(clippy::borrowed_box)]
-pub async fn upload(
+pub async fn prepare_upload(
     config: &Config,
     system_info: &SystemInfo,
     provider: &Box<dyn CIProvider>,
     run_data: &RunData,
     executor_name: ExecutorName,
-) -> Result<UploadResult> {
-    let (archive_buffer, archive_hash) = get_profile_archive_buffer(run_data).await?;
+) -> Result<(UploadMetadata, ProfileArchive)> {
+    let archive_settings = ArchiveSettings {
+        compression: config.profile_compression.clone(),
+        max_size: config.max_archive_size,
+        redaction: config.redaction.clone(),
+    };
+    let archive = create_profile_archive(&run_data.profile_folder, &archive_settings).await?;
+    fs::write(
+        run_data.profile_folder.join(ARCHIVE_SETTINGS_FILE_NAME),
+        serde_json::to_vec_pretty(&archive_settings)?,
+    )
+    .context("Failed to persist the archive settings in the profile folder")?;
 
     debug!("CI provider detected: {:#?}", provider.get_provider_name());
 
     let upload_metadata =
-        provider.get_upload_metadata(config, system_info, &archive_hash, executor_name)?;
+        provider.get_upload_metadata(config, system_info, &archive, executor_name)?;
     debug!("Upload metadata: {:#?}", upload_metadata);
+    fs::write(
+        run_data.profile_folder.join(UPLOAD_METADATA_FILE_NAME),
+        serde_json::to_vec_pretty(&upload_metadata)?,
+    )
+    .context("Failed to persist the upload metadata in the profile folder")?;
+    Ok((upload_metadata, archive))
+}
+
+/// Upload a profile archive along with its metadata, throttled to `rate_limit` bytes per second if
+/// any
+pub async fn upload_archive(
+    upload_url: &Url,
+    token: Option<&String>,
+    upload_metadata: &UploadMetadata,
+    archive: ProfileArchive,
+    rate_limit: Option<u64>,
+) -> Result<UploadResult> {
     info!(
         "Linked repository: {}\n",
         style(format!(
@@ -124,23 +183,44 @@ pub async fn upload(
     }
 
     info!("Preparing upload...");
-    let upload_data = retrieve_upload_data(config, &upload_metadata).await?;
+    let upload_data = retrieve_upload_data(upload_url, token, upload_metadata).await?;
     debug!("runId: {}", upload_data.run_id);
 
     info!("Uploading performance data...");
-    debug!("Uploading {} bytes...", archive_buffer.len());
-    upload_archive_buffer(&upload_data, archive_buffer, &archive_hash).await?;
+    let archive_size = archive.buffer.len();
+    debug!("Uploading {} bytes...", archive_size);
+    upload_archive_buffer(&upload_data, &archive, rate_limit).await?;
     info!("Performance data uploaded");
 
     Ok(UploadResult {
         run_id: upload_data.run_id,
+        archive_size,
     })
 }
 
+#[allow(clippy::borrowed_box)]
+pub async fn upload(
+    config: &Config,
+    system_info: &SystemInfo,
+    provider: &Box<dyn CIProvider>,
+    run_data: &RunData,
+    executor_name: ExecutorName,
+) -> Result<UploadResult> {
+    let (upload_metadata, archive) =
+        prepare_upload(config, system_info, provider, run_data, executor_name).await?;
+    upload_archive(
+        &config.upload_url,
+        config.token.as_ref(),
+        &upload_metadata,
+        archive,
+        config.upload_rate_limit,
+    )
+    .await
+}
+
 #[cfg(test)]
 mod tests {
     use temp_env::async_with_vars;
-    use url::Url;
 
     use super::*;
     use std::path::PathBuf;
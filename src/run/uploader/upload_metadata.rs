@@ -1,40 +1,120 @@
+use indicatif::HumanBytes;
+use itertools::Itertools;
 use serde_json::json;
 
-use super::UploadMetadata;
+use super::{ProfileArchive, UploadMetadata};
+#[cfg(test)]
+use crate::run::{
+    check_system::SystemInfo,
+    ci_provider::interfaces::{CIProviderMetadata, GhData, RepositoryProvider, RunEvent, Sender},
+    instruments::InstrumentName,
+    runner::ExecutorName,
+    uploader::{ProfileEncoding, Runner},
+};
+
+/// Name of the upload metadata file when stored outside of the upload, e.g. in a bundle or in the
+/// profile folder
+pub const UPLOAD_METADATA_FILE_NAME: &str = "upload-metadata.json";
 
 impl UploadMetadata {
     pub fn get_hash(&self) -> String {
         let upload_metadata_string = json!(&self).to_string();
         sha256::digest(upload_metadata_string)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use insta::assert_json_snapshot;
+    /// Human readable summary of the upload and of the files of its profile archive
+    pub fn get_summary(&self, archive: &ProfileArchive) -> String {
+        let files = archive
+            .manifest
+            .as_ref()
+            .map(|manifest| manifest.files.as_slice())
+            .unwrap_or_default();
+        let ci = &self.ci_provider_metadata;
+        let optional = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".into());
+        let instruments = if self.runner.instruments.is_empty() {
+            "none".into()
+        } else {
+            self.runner
+                .instruments
+                .iter()
+                .map(|instrument| format!("{:?}", instrument))
+                .join(", ")
+        };
+        let system_info = &self.runner.system_info;
 
-    use crate::run::{
-        check_system::SystemInfo,
-        ci_provider::interfaces::{
-            CIProviderMetadata, GhData, RepositoryProvider, RunEvent, Sender,
-        },
-        instruments::InstrumentName,
-        runner::ExecutorName,
-        uploader::{Runner, UploadMetadata},
-    };
+        let mut summary = vec![
+            format!(
+                "Repository: {}/{} ({:?})",
+                ci.owner, ci.repository, self.repository_provider
+            ),
+            format!("Platform: {}", self.platform),
+            format!("Event: {:?}", ci.event),
+            format!("Ref: {}", ci.ref_),
+            format!("Head ref: {}", optional(&ci.head_ref)),
+            format!("Base ref: {}", optional(&ci.base_ref)),
+            format!("Commit: {}", self.commit_hash),
+            format!(
+                "Runner: {} {} ({} executor)",
+                self.runner.name,
+                self.runner.version,
+                self.runner.executor.to_string()
+            ),
+            format!("Instruments: {}", instruments),
+            format!(
+                "System: {} {} ({}), {} cores, {}GB of memory",
+                system_info.os,
+                system_info.os_version,
+                system_info.arch,
+                system_info.cpu_cores,
+                system_info.total_memory_gb
+            ),
+            format!(
+                "Profile archive: {} ({:?}, {} files)",
+                HumanBytes(archive.buffer.len() as u64),
+                archive.encoding,
+                files.len()
+            ),
+        ];
+        summary.extend(
+            files
+                .iter()
+                .map(|file| format!("  {} ({})", file.path, HumanBytes(file.size))),
+        );
+        if !archive.excluded_files.is_empty() {
+            summary.push(format!(
+                "Left out of the profile archive: {} files",
+                archive.excluded_files.len()
+            ));
+            summary.extend(
+                archive
+                    .excluded_files
+                    .iter()
+                    .map(|path| format!("  {}", path)),
+            );
+        }
+        summary.join("\n")
+    }
+}
 
-    #[test]
-    fn test_get_metadata_hash() {
-        let upload_metadata = UploadMetadata {
+#[cfg(test)]
+impl UploadMetadata {
+    /// Constructs a new `UploadMetadata` of a GitHub Actions run for testing purposes
+    pub fn test() -> Self {
+        UploadMetadata {
             repository_provider: RepositoryProvider::GitHub,
             version: Some(5),
             tokenless: true,
             profile_md5: "jp/k05RKuqP3ERQuIIvx4Q==".into(),
+            profile_encoding: ProfileEncoding::Gzip,
+            profile_sha256: None,
+            run_group: None,
             runner: Runner {
                 name: "codspeed-runner".into(),
                 version: "2.1.0".into(),
                 instruments: vec![InstrumentName::MongoDB],
                 executor: ExecutorName::Valgrind,
+                cpu_affinity: None,
+                resource_limits: None,
                 system_info: SystemInfo::test(),
             },
             platform: "github-actions".into(),
@@ -56,8 +136,24 @@ mod tests {
                 }),
                 gl_data: None,
                 repository_root_path: "/home/runner/work/codspeed-node/codspeed-node/".into(),
+                run_url: None,
             },
-        };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use insta::assert_json_snapshot;
+
+    use std::collections::BTreeMap;
+
+    use crate::run::uploader::manifest::{ArchiveManifest, ManifestFile};
+    use crate::run::uploader::{ProfileArchive, ProfileEncoding, UploadMetadata};
+
+    #[test]
+    fn test_get_metadata_hash() {
+        let upload_metadata = UploadMetadata::test();
 
         let hash = upload_metadata.get_hash();
         assert_eq!(
@@ -66,4 +162,38 @@ mod tests {
         );
         assert_json_snapshot!(upload_metadata);
     }
+
+    #[test]
+    fn test_get_summary() {
+        let archive = ProfileArchive {
+            manifest: Some(ArchiveManifest {
+                tools: BTreeMap::new(),
+                files: vec![ManifestFile {
+                    path: "1234.out".into(),
+                    size: 4096,
+                    sha256: "".into(),
+                }],
+            }),
+            excluded_files: vec!["perf-1234.map".into()],
+            ..ProfileArchive::from_buffer(vec![0; 2048], ProfileEncoding::Gzip)
+        };
+
+        assert_eq!(
+            UploadMetadata::test().get_summary(&archive),
+            "Repository: CodSpeedHQ/codspeed-node (GitHub)
+Platform: github-actions
+Event: PullRequest
+Ref: refs/pull/29/merge
+Head ref: chore/native-action-runner
+Base ref: main
+Commit: 5bd77cb0da72bef094893ed45fb793ff16ecfbe3
+Runner: codspeed-runner 2.1.0 (valgrind executor)
+Instruments: MongoDB
+System: ubuntu 20.04 (x86_64), 2 cores, 8GB of memory
+Profile archive: 2.00 KiB (Gzip, 1 files)
+  1234.out (4.00 KiB)
+Left out of the profile archive: 1 files
+  perf-1234.map"
+        );
+    }
 }